@@ -0,0 +1,80 @@
+use clap::ValueEnum;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+/// How the wait between attempts grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Wait the initial delay every time
+    Fixed,
+    /// Double the delay after each attempt, up to the max
+    Exponential,
+    /// Like exponential, but wait a random 50-100% of each delay so many
+    /// senders don't retry in lockstep
+    Jitter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub strategy: Strategy,
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// The wait after attempt number `attempt`, counting from zero.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max)
+            .min(self.max);
+        match self.strategy {
+            Strategy::Fixed => self.initial.min(self.max),
+            Strategy::Exponential => exponential,
+            Strategy::Jitter => {
+                let fraction = RandomState::new().hash_one(attempt) as f64 / u64::MAX as f64;
+                exponential.mul_f64(0.5 + fraction / 2.0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backoff(strategy: Strategy) -> Backoff {
+        Backoff {
+            strategy,
+            initial: Duration::from_secs(5),
+            max: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn test_fixed() {
+        let fixed = backoff(Strategy::Fixed);
+        assert_eq!(fixed.delay(0), Duration::from_secs(5));
+        assert_eq!(fixed.delay(7), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_exponential_caps_at_max() {
+        let exponential = backoff(Strategy::Exponential);
+        let delays: Vec<u64> = (0..5).map(|i| exponential.delay(i).as_secs()).collect();
+        assert_eq!(delays, [5, 10, 20, 30, 30]);
+        assert_eq!(exponential.delay(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_half_to_full() {
+        let jitter = backoff(Strategy::Jitter);
+        for attempt in 0..20 {
+            let delay = jitter.delay(attempt);
+            let full = backoff(Strategy::Exponential).delay(attempt);
+            assert!(delay >= full / 2 && delay <= full, "{delay:?} vs {full:?}");
+        }
+    }
+}
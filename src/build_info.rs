@@ -0,0 +1,69 @@
+/// What went into this binary, for bug reports and fleet inventories.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Empty when the build didn't happen in a git checkout.
+pub const GIT_COMMIT: &str = env!("WOL_GIT_COMMIT");
+pub const BUILD_DATE: &str = env!("WOL_BUILD_DATE");
+pub const TARGET: &str = env!("WOL_TARGET");
+
+/// The optional Cargo features this binary was built with.
+pub fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "serde") {
+        features.push("serde");
+    }
+    if cfg!(feature = "test-util") {
+        features.push("test-util");
+    }
+    features
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders the build information as a single-line JSON object.
+pub fn json() -> String {
+    let commit = if GIT_COMMIT.is_empty() {
+        String::from("null")
+    } else {
+        json_string(GIT_COMMIT)
+    };
+    let features: Vec<String> = features().into_iter().map(json_string).collect();
+    format!(
+        "{{\"name\":{},\"version\":{},\"git_commit\":{commit},\"build_date\":{},\"target\":{},\"features\":[{}]}}",
+        json_string(env!("CARGO_PKG_NAME")),
+        json_string(VERSION),
+        json_string(BUILD_DATE),
+        json_string(TARGET),
+        features.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string_escapes() {
+        assert_eq!(json_string("a\"b\\c\n"), r#""a\"b\\c\u000a""#);
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let json = json();
+        assert!(json.starts_with(r#"{"name":"wake-on-lan","version":""#));
+        assert!(json.contains(&format!(r#""target":"{TARGET}""#)));
+        assert!(json.ends_with("]}"));
+        assert_eq!(BUILD_DATE.len(), "2026-01-01".len());
+    }
+}
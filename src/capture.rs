@@ -0,0 +1,209 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LINKTYPE_RAW: u32 = 101;
+const SNAPLEN: u32 = 65535;
+
+/// Appends transmitted packets to a pcap capture, reconstructing the IP and
+/// UDP headers around each payload so the file opens in any pcap tool.
+pub struct Writer {
+    file: File,
+}
+
+impl Writer {
+    /// Opens `path` for appending, writing the pcap header if the file is new
+    /// or empty. An existing capture must be a raw-IP capture written by us.
+    pub fn open(path: &Path) -> io::Result<Writer> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&file_header())?;
+        } else {
+            let mut header = [0; 24];
+            file.seek(SeekFrom::Start(0))?;
+            file.read_exact(&mut header)?;
+            if header != file_header() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "existing file is not a raw-IP pcap capture written by wake-on-lan",
+                ));
+            }
+        }
+        Ok(Writer { file })
+    }
+
+    pub fn write_udp(
+        &mut self,
+        time: SystemTime,
+        source: SocketAddr,
+        destination: SocketAddr,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let packet = udp_packet(source, destination, payload);
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut record = Vec::with_capacity(16 + packet.len());
+        record.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&packet);
+        self.file.write_all(&record)
+    }
+}
+
+fn file_header() -> [u8; 24] {
+    let mut header = [0; 24];
+    header[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    header[4..6].copy_from_slice(&2u16.to_le_bytes());
+    header[6..8].copy_from_slice(&4u16.to_le_bytes());
+    header[16..20].copy_from_slice(&SNAPLEN.to_le_bytes());
+    header[20..24].copy_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    header
+}
+
+/// Builds the IPv4 or IPv6 datagram carrying `payload`. An unbound (wildcard)
+/// source address is recorded as the unspecified address of the destination's
+/// family.
+pub fn udp_packet(source: SocketAddr, destination: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let udp_len = (8 + payload.len()) as u16;
+    let mut udp = Vec::with_capacity(usize::from(udp_len));
+    udp.extend_from_slice(&source.port().to_be_bytes());
+    udp.extend_from_slice(&destination.port().to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(payload);
+
+    match destination.ip() {
+        IpAddr::V4(dst) => {
+            let src = match source.ip() {
+                IpAddr::V4(src) => src,
+                IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+            };
+            let mut pseudo = Vec::new();
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+            pseudo.extend_from_slice(&[0, 17]);
+            pseudo.extend_from_slice(&udp_len.to_be_bytes());
+            set_udp_checksum(&mut udp, &pseudo);
+
+            let mut packet = vec![0x45, 0];
+            packet.extend_from_slice(&(20 + udp_len).to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0x40, 0, 64, 17, 0, 0]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+            let checksum = checksum(&packet);
+            packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+            packet.extend_from_slice(&udp);
+            packet
+        }
+        IpAddr::V6(dst) => {
+            let src = match source.ip() {
+                IpAddr::V6(src) => src,
+                IpAddr::V4(_) => Ipv6Addr::UNSPECIFIED,
+            };
+            let mut pseudo = Vec::new();
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+            pseudo.extend_from_slice(&u32::from(udp_len).to_be_bytes());
+            pseudo.extend_from_slice(&[0, 0, 0, 17]);
+            set_udp_checksum(&mut udp, &pseudo);
+
+            let mut packet = vec![0x60, 0, 0, 0];
+            packet.extend_from_slice(&udp_len.to_be_bytes());
+            packet.extend_from_slice(&[17, 64]);
+            packet.extend_from_slice(&src.octets());
+            packet.extend_from_slice(&dst.octets());
+            packet.extend_from_slice(&udp);
+            packet
+        }
+    }
+}
+
+fn set_udp_checksum(udp: &mut [u8], pseudo_header: &[u8]) {
+    let mut data = pseudo_header.to_vec();
+    data.extend_from_slice(udp);
+    // A computed checksum of zero is transmitted as all ones (RFC 768).
+    let checksum = match checksum(&data) {
+        0 => 0xFFFF,
+        sum => sum,
+    };
+    udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// The Internet checksum (RFC 1071).
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        // Example header from RFC 1071 section 3.
+        assert_eq!(
+            checksum(&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]),
+            !0xddf2
+        );
+    }
+
+    #[test]
+    fn test_udp_packet_ipv4() {
+        let source: SocketAddr = "192.168.1.2:40000".parse().unwrap();
+        let destination: SocketAddr = "255.255.255.255:9".parse().unwrap();
+        let packet = udp_packet(source, destination, &[0xFF; 102]);
+        assert_eq!(packet.len(), 20 + 8 + 102);
+        assert_eq!(packet[9], 17);
+        assert_eq!(&packet[16..20], &[255, 255, 255, 255]);
+        assert_eq!(checksum(&packet[..20]), 0);
+        assert_eq!(&packet[22..24], &9u16.to_be_bytes());
+        assert_eq!(&packet[28..], &[0xFF; 102]);
+    }
+
+    #[test]
+    fn test_udp_packet_ipv6() {
+        let source: SocketAddr = "[::]:40000".parse().unwrap();
+        let destination: SocketAddr = "[ff02::1]:9".parse().unwrap();
+        let packet = udp_packet(source, destination, &[0xFF; 102]);
+        assert_eq!(packet.len(), 40 + 8 + 102);
+        assert_eq!(packet[0] >> 4, 6);
+        assert_eq!(&packet[4..6], &110u16.to_be_bytes());
+        assert_ne!(&packet[46..48], &[0, 0]);
+    }
+
+    #[test]
+    fn test_writer_appends_readable_capture() {
+        let path = std::env::temp_dir().join(format!("wol-capture-{}.pcap", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let source: SocketAddr = "0.0.0.0:40000".parse().unwrap();
+        let destination: SocketAddr = "192.168.1.255:9".parse().unwrap();
+        let magic = wake_on_lan::magic_packet([0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89].into());
+
+        for _ in 0..2 {
+            let mut writer = Writer::open(&path).unwrap();
+            writer
+                .write_udp(SystemTime::now(), source, destination, &magic)
+                .unwrap();
+        }
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(data.len(), 24 + 2 * (16 + 20 + 8 + 102));
+        let entries = crate::import::pcap::parse(&data).unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+}
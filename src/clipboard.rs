@@ -0,0 +1,71 @@
+use crate::import;
+use std::process::Command;
+
+/// Clipboard readers to try in order, as program and arguments.
+fn readers() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbpaste"]]
+    } else if cfg!(windows) {
+        &[&["powershell", "-NoProfile", "-Command", "Get-Clipboard"]]
+    } else {
+        &[
+            &["wl-paste", "--no-newline"],
+            &["xclip", "-selection", "clipboard", "-o"],
+            &["xsel", "--clipboard", "--output"],
+        ]
+    }
+}
+
+/// Reads the system clipboard as text through the platform's paste tool.
+pub fn read() -> Result<String, String> {
+    let mut tried = Vec::new();
+    for reader in readers() {
+        if let Ok(output) = Command::new(reader[0]).args(&reader[1..]).output()
+            && output.status.success()
+        {
+            return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+        }
+        tried.push(reader[0]);
+    }
+    Err(format!(
+        "failed to read the clipboard (tried {})",
+        tried.join(", ")
+    ))
+}
+
+/// Finds the first MAC address in free text, such as a row copied out of a
+/// router's web page. Any form the importers read is accepted, including
+/// unpadded octets and Cisco's dotted form.
+pub fn find_mac(text: &str) -> Option<[u8; 6]> {
+    import::text::mac_tokens(text)
+        .filter_map(|token| {
+            import::parse_mac(token).or_else(|| import::text::parse_dotted_mac(token))
+        })
+        .find(|mac| *mac != [0; 6])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_mac() {
+        assert_eq!(
+            find_mac("desktop\tB8-AE-ED-9C-C7-89\t192.168.1.40\n"),
+            Some([0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89])
+        );
+        assert_eq!(
+            find_mac("MAC: 00:11:22:33:44:55, lease 12h"),
+            Some([0x00, 0x11, 0x22, 0x33, 0x44, 0x55])
+        );
+        assert_eq!(
+            find_mac("Gi0/3  b8ae.ed9c.c789  DYNAMIC"),
+            Some([0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89])
+        );
+        assert_eq!(
+            find_mac("? (192.168.1.40) at b8:ae:ed:9c:c7:9 on en0"),
+            Some([0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x09])
+        );
+        assert_eq!(find_mac("no address here"), None);
+    }
+}
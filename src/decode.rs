@@ -0,0 +1,206 @@
+use std::net::Ipv4Addr;
+use wake_on_lan::MacAddr;
+
+/// What could be recovered from a captured payload, along with everything that
+/// keeps it from being a well-formed magic packet.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Decoded {
+    pub mac: Option<[u8; 6]>,
+    pub secure_on: Option<Vec<u8>>,
+    pub problems: Vec<String>,
+}
+
+/// Parses a hex dump, tolerating whitespace, `:`/`-` separators and a `0x` prefix.
+pub fn parse_hex(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+        .unwrap_or(input);
+    let hex: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':' && *c != '-')
+        .collect();
+    if hex.is_empty() {
+        return Err(String::from("no hex digits found"));
+    }
+    if let Some(c) = hex.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex digit '{c}'"));
+    }
+    if !hex.len().is_multiple_of(2) {
+        return Err(String::from("odd number of hex digits"));
+    }
+    Ok(hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+        .collect())
+}
+
+/// Formats a SecureOn password the way `--password-file` takes it: 6 bytes
+/// like a MAC address, 4 like an IPv4 address.
+pub fn format_secure_on(password: &[u8]) -> String {
+    if let Ok(bytes) = <[u8; 6]>::try_from(password) {
+        MacAddr::from(bytes).to_string()
+    } else if let Ok(bytes) = <[u8; 4]>::try_from(password) {
+        Ipv4Addr::from(bytes).to_string()
+    } else {
+        format!("{password:02x?}")
+    }
+}
+
+pub fn decode(payload: &[u8]) -> Decoded {
+    let mut decoded = Decoded::default();
+
+    let start = if payload.starts_with(&[0xFF; 6]) {
+        0
+    } else {
+        match payload.windows(6).position(|w| w == [0xFF; 6]) {
+            Some(offset) => {
+                decoded.problems.push(format!(
+                    "sync stream starts at offset {offset} instead of 0 ({offset} leading bytes)"
+                ));
+                offset
+            }
+            None => {
+                decoded
+                    .problems
+                    .push(String::from("no 6 x 0xFF sync stream found"));
+                return decoded;
+            }
+        }
+    };
+    let packet = &payload[start..];
+
+    if packet.len() < 12 {
+        decoded.problems.push(format!(
+            "payload ends {} bytes after the sync stream, before a full MAC address",
+            packet.len() - 6
+        ));
+        return decoded;
+    }
+    let mac: [u8; 6] = packet[6..12].try_into().unwrap();
+    decoded.mac = Some(mac);
+
+    let repetitions = (packet.len() - 6) / 6;
+    if repetitions < 16 {
+        decoded.problems.push(format!(
+            "payload is {} bytes, too short for 16 MAC repetitions (102 bytes)",
+            packet.len()
+        ));
+    }
+    for i in 1..repetitions.min(16) {
+        let offset = start + 6 + i * 6;
+        let repetition: [u8; 6] = packet[6 + i * 6..12 + i * 6].try_into().unwrap();
+        if repetition != mac {
+            decoded.problems.push(format!(
+                "repetition {} at offset {offset} is {}, expected {}",
+                i + 1,
+                MacAddr::from(repetition),
+                MacAddr::from(mac)
+            ));
+        }
+    }
+
+    if packet.len() > 102 {
+        let suffix = &packet[102..];
+        match suffix.len() {
+            4 | 6 => decoded.secure_on = Some(suffix.to_vec()),
+            n => decoded.problems.push(format!(
+                "{n} trailing bytes after the MAC repetitions; a SecureOn password is 4 or 6 bytes"
+            )),
+        }
+    }
+
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89];
+
+    fn magic_packet() -> Vec<u8> {
+        wake_on_lan::magic_packet(MAC.into()).to_vec()
+    }
+
+    #[test]
+    fn test_parse_hex_separators() {
+        assert_eq!(
+            parse_hex("0xff ff:00-01").unwrap(),
+            [0xff, 0xff, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_parse_hex_invalid() {
+        assert!(parse_hex("").is_err());
+        assert!(parse_hex("fff").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_format_secure_on() {
+        assert_eq!(format_secure_on(&[1, 2, 3, 4, 5, 10]), "01:02:03:04:05:0a");
+        assert_eq!(format_secure_on(&[192, 168, 1, 1]), "192.168.1.1");
+    }
+
+    #[test]
+    fn test_decode_valid() {
+        let decoded = decode(&magic_packet());
+        assert_eq!(decoded.mac, Some(MAC));
+        assert_eq!(decoded.secure_on, None);
+        assert!(decoded.problems.is_empty());
+    }
+
+    #[test]
+    fn test_decode_secure_on() {
+        let mut packet = magic_packet();
+        packet.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        let decoded = decode(&packet);
+        assert_eq!(decoded.secure_on, Some(vec![1, 2, 3, 4, 5, 6]));
+        assert!(decoded.problems.is_empty());
+    }
+
+    #[test]
+    fn test_decode_bad_trailer() {
+        let mut packet = magic_packet();
+        packet.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(decode(&packet).problems.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_no_sync_stream() {
+        let decoded = decode(&[0x00; 102]);
+        assert_eq!(decoded.mac, None);
+        assert_eq!(decoded.problems.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_offset_sync_stream() {
+        let mut packet = vec![0x00, 0x01];
+        packet.extend_from_slice(&magic_packet());
+        let decoded = decode(&packet);
+        assert_eq!(decoded.mac, Some(MAC));
+        assert_eq!(decoded.problems.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_corrupt_repetition() {
+        let mut packet = magic_packet();
+        packet[6 + 6 * 4] = 0x00;
+        let decoded = decode(&packet);
+        assert_eq!(decoded.mac, Some(MAC));
+        assert_eq!(decoded.problems.len(), 1);
+        assert!(decoded.problems[0].contains("repetition 5"));
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let packet = magic_packet();
+        let decoded = decode(&packet[..60]);
+        assert_eq!(decoded.mac, Some(MAC));
+        assert_eq!(decoded.problems.len(), 1);
+    }
+}
@@ -0,0 +1,161 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+/// The parts of a DNS response we act on.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Response {
+    pub rcode: u8,
+    pub addresses: Vec<IpAddr>,
+}
+
+/// Builds a query message asking for each of `types` for `name`. The same
+/// wire format serves unicast DNS, mDNS and LLMNR.
+pub fn query(id: u16, name: &str, types: &[u16], recursion_desired: bool) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&[if recursion_desired { 0x01 } else { 0x00 }, 0x00]);
+    message.extend_from_slice(&(types.len() as u16).to_be_bytes());
+    message.extend_from_slice(&[0; 6]);
+    for qtype in types {
+        for label in name.trim_end_matches('.').split('.') {
+            let label = &label.as_bytes()[..label.len().min(63)];
+            message.push(label.len() as u8);
+            message.extend_from_slice(label);
+        }
+        message.push(0);
+        message.extend_from_slice(&qtype.to_be_bytes());
+        message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    }
+    message
+}
+
+/// Parses a response to the query with the given `id`, collecting the A and
+/// AAAA records from the answer section.
+pub fn parse_response(message: &[u8], id: u16) -> Result<Response, String> {
+    if message.len() < 12 {
+        return Err(String::from("truncated DNS header"));
+    }
+    if u16::from_be_bytes([message[0], message[1]]) != id {
+        return Err(String::from("DNS response ID does not match the query"));
+    }
+    if message[2] & 0x80 == 0 {
+        return Err(String::from("DNS message is not a response"));
+    }
+    let mut response = Response {
+        rcode: message[3] & 0x0F,
+        addresses: Vec::new(),
+    };
+    let questions = u16::from_be_bytes([message[4], message[5]]);
+    let answers = u16::from_be_bytes([message[6], message[7]]);
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = skip_name(message, offset)? + 4;
+    }
+    for _ in 0..answers {
+        offset = skip_name(message, offset)?;
+        let Some(fixed) = message.get(offset..offset + 10) else {
+            return Err(String::from("truncated DNS answer"));
+        };
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        // mDNS sets the top bit of the class as the cache-flush flag.
+        let class = u16::from_be_bytes([fixed[2], fixed[3]]) & 0x7FFF;
+        let length = usize::from(u16::from_be_bytes([fixed[8], fixed[9]]));
+        offset += 10;
+        let Some(data) = message.get(offset..offset + length) else {
+            return Err(String::from("truncated DNS record data"));
+        };
+        match (rtype, class, length) {
+            (TYPE_A, CLASS_IN, 4) => {
+                let octets: [u8; 4] = data.try_into().unwrap();
+                response.addresses.push(IpAddr::V4(Ipv4Addr::from(octets)));
+            }
+            (TYPE_AAAA, CLASS_IN, 16) => {
+                let octets: [u8; 16] = data.try_into().unwrap();
+                response.addresses.push(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            _ => {}
+        }
+        offset += length;
+    }
+    Ok(response)
+}
+
+/// Returns the offset just past the (possibly compressed) name at `offset`.
+pub fn skip_name(message: &[u8], mut offset: usize) -> Result<usize, String> {
+    loop {
+        let Some(&length) = message.get(offset) else {
+            return Err(String::from("truncated DNS name"));
+        };
+        match length {
+            0 => return Ok(offset + 1),
+            // A compression pointer ends the name.
+            l if l & 0xC0 == 0xC0 => return Ok(offset + 2),
+            l => offset += 1 + usize::from(l),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(id: u16, rcode: u8, answers: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut message = query(id, "nas.local", &[TYPE_A], false);
+        message[2] = 0x84;
+        message[3] = rcode;
+        message[7] = answers.len() as u8;
+        for (rtype, data) in answers {
+            // Pointer back to the question name at offset 12.
+            message.extend_from_slice(&[0xC0, 12]);
+            message.extend_from_slice(&rtype.to_be_bytes());
+            message.extend_from_slice(&[0x80, 0x01, 0, 0, 0, 120]);
+            message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            message.extend_from_slice(data);
+        }
+        message
+    }
+
+    #[test]
+    fn test_query_encoding() {
+        let message = query(0x1234, "nas.local.", &[TYPE_A], true);
+        assert_eq!(&message[..12], &[0x12, 0x34, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&message[12..23], b"\x03nas\x05local\x00");
+        assert_eq!(&message[23..], &[0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_parse_response_addresses() {
+        let ipv6 = "fe80::1".parse::<Ipv6Addr>().unwrap().octets();
+        let message = response(7, 0, &[(TYPE_A, &[192, 168, 1, 40]), (TYPE_AAAA, &ipv6)]);
+        let parsed = parse_response(&message, 7).unwrap();
+        assert_eq!(parsed.rcode, 0);
+        assert_eq!(
+            parsed.addresses,
+            [
+                "192.168.1.40".parse::<IpAddr>().unwrap(),
+                "fe80::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_nxdomain() {
+        let parsed = parse_response(&response(7, 3, &[]), 7).unwrap();
+        assert_eq!(parsed.rcode, RCODE_NXDOMAIN);
+        assert!(parsed.addresses.is_empty());
+    }
+
+    #[test]
+    fn test_parse_response_rejects_mismatch() {
+        assert!(parse_response(&response(7, 0, &[]), 8).is_err());
+        assert!(parse_response(&query(7, "nas", &[TYPE_A], false), 7).is_err());
+        let mut truncated = response(7, 0, &[(TYPE_A, &[192, 168, 1, 40])]);
+        truncated.truncate(truncated.len() - 2);
+        assert!(parse_response(&truncated, 7).is_err());
+    }
+}
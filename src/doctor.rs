@@ -0,0 +1,227 @@
+use crate::{neighbor, probe, resolve, route};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+use wake_on_lan::MacAddr;
+
+/// How much a finding explains a wake that doesn't arrive, most likely first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Problem,
+    Warning,
+    Info,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+fn finding(severity: Severity, message: String) -> Finding {
+    Finding { severity, message }
+}
+
+/// Sends an empty datagram to the discard port of `destination`, with
+/// broadcasting enabled.
+pub fn send_empty(destination: SocketAddr) -> io::Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+    socket.send_to(&[], destination)?;
+    Ok(())
+}
+
+fn send_finding(what: &str, result: io::Result<()>, ok: String) -> Finding {
+    match result {
+        Ok(()) => finding(Severity::Info, ok),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => finding(
+            Severity::Problem,
+            format!(
+                "sending to {what} was refused ({e}); a firewall rule probably drops outgoing broadcasts"
+            ),
+        ),
+        Err(e) => finding(Severity::Problem, format!("sending to {what} failed: {e}")),
+    }
+}
+
+/// Checks that broadcasts can leave through each directly attached network,
+/// sending with `send`. Without a routing table only 255.255.255.255 is
+/// checked.
+pub fn check_networks(
+    routes: Result<&[route::Route], &io::Error>,
+    send: impl Fn(SocketAddr) -> io::Result<()>,
+) -> Vec<Finding> {
+    let mut findings = vec![send_finding(
+        "255.255.255.255",
+        send((Ipv4Addr::BROADCAST, 9).into()),
+        String::from("the default broadcast address 255.255.255.255 is usable"),
+    )];
+    let routes = match routes {
+        Ok(routes) => routes,
+        Err(e) => {
+            findings.push(finding(
+                Severity::Warning,
+                format!("routes unavailable ({e}), so attached networks weren't checked"),
+            ));
+            return findings;
+        }
+    };
+    let attached: Vec<&route::Route> = routes
+        .iter()
+        .filter(|r| r.gateway.is_none() && !r.destination.is_loopback() && r.prefix_len() < 31)
+        .collect();
+    if attached.is_empty() {
+        findings.push(finding(
+            Severity::Problem,
+            String::from("no directly attached IPv4 network, so broadcasts reach no LAN"),
+        ));
+    }
+    for network in attached {
+        let broadcast = network.broadcast();
+        findings.push(send_finding(
+            &format!("{broadcast} on {}", network.interface),
+            send((broadcast, 9).into()),
+            format!(
+                "{} ({}/{}) accepts broadcasts; for hosts there use --address {broadcast}",
+                network.interface,
+                network.destination,
+                network.prefix_len()
+            ),
+        ));
+    }
+    findings
+}
+
+/// Checks how `host` can be reached: its name, its route, whether its MAC is
+/// in the neighbor table and whether it answers on `port`. Routes are skipped
+/// when `routes` is `None`.
+pub fn check_host(host: &str, port: u16, routes: Option<&[route::Route]>) -> Vec<Finding> {
+    let timeout = Duration::from_secs(2);
+    let addrs = match resolve::resolve(host, port) {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            return vec![finding(
+                Severity::Problem,
+                format!(
+                    "{e}; a sleeping host's name often doesn't resolve either, so give --address a broadcast address"
+                ),
+            )];
+        }
+    };
+    let awake = probe::host(&addrs, timeout);
+    let mut findings = Vec::new();
+    for addr in &addrs {
+        let IpAddr::V4(ip) = addr.ip() else {
+            findings.push(finding(
+                Severity::Info,
+                format!("{}: only IPv4 routes and neighbors are checked", addr.ip()),
+            ));
+            continue;
+        };
+        if ip.is_loopback() {
+            findings.push(finding(
+                Severity::Info,
+                format!("{ip} is this machine's loopback address"),
+            ));
+            continue;
+        }
+        let route = match routes.map(|routes| route::lookup(routes, ip)) {
+            Some(Some(route)) => Some(route),
+            Some(None) => {
+                findings.push(finding(Severity::Problem, format!("no route to {ip}")));
+                continue;
+            }
+            None => None,
+        };
+        if let Some(gateway) = route.and_then(|route| route.gateway) {
+            findings.push(finding(
+                Severity::Warning,
+                format!(
+                    "{ip} is behind the router {gateway}; routers don't forward broadcasts by default, so use a directed broadcast the router forwards, a TCP relay or --routeros"
+                ),
+            ));
+            continue;
+        }
+        match neighbor::resolve(ip.into(), timeout) {
+            Ok(Some(neighbor)) => {
+                let mac = MacAddr::from(neighbor.mac);
+                let wake = match route {
+                    Some(route) => format!("--mac {mac} --address {}", route.broadcast()),
+                    None => format!("--mac {mac}"),
+                };
+                findings.push(finding(
+                    Severity::Info,
+                    format!("{ip} is at {mac} on {}; wake it with {wake}", neighbor.interface),
+                ));
+            }
+            Ok(None) if !awake => findings.push(finding(
+                Severity::Warning,
+                format!(
+                    "{ip} has no neighbor entry; the host is asleep or gone, so take its MAC from an inventory (see import)"
+                ),
+            )),
+            Ok(None) => {}
+            Err(e) => findings.push(finding(
+                Severity::Warning,
+                format!("failed to read the neighbor table: {e}"),
+            )),
+        }
+    }
+    findings.push(if awake {
+        finding(
+            Severity::Info,
+            format!("{host} is awake and answers on port {port}"),
+        )
+    } else {
+        finding(
+            Severity::Warning,
+            format!(
+                "{host} doesn't answer on port {port}; if it is asleep and won't wake, check that Wake-on-LAN is enabled in its firmware and OS (on Linux: ethtool -s <interface> wol g)"
+            ),
+        )
+    });
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use route::tests::network;
+
+    #[test]
+    fn test_check_networks() {
+        let routes = [
+            network([0, 0, 0, 0], [0, 0, 0, 0], Some([192, 168, 1, 1])),
+            network([192, 168, 1, 0], [255, 255, 255, 0], None),
+        ];
+        let findings = check_networks(Ok(&routes), |destination| {
+            if destination.ip() == IpAddr::V4(Ipv4Addr::BROADCAST) {
+                Err(io::ErrorKind::PermissionDenied.into())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].severity, Severity::Problem);
+        assert!(findings[0].message.contains("firewall"));
+        assert_eq!(findings[1].severity, Severity::Info);
+        assert!(findings[1].message.contains("--address 192.168.1.255"));
+    }
+
+    #[test]
+    fn test_check_networks_none_attached() {
+        let routes = [network([0, 0, 0, 0], [0, 0, 0, 0], Some([10, 0, 0, 1]))];
+        let findings = check_networks(Ok(&routes), |_| Ok(()));
+        assert_eq!(findings[1].severity, Severity::Problem);
+    }
+
+    #[test]
+    fn test_check_networks_routes_unavailable() {
+        let error = io::Error::from(io::ErrorKind::NotFound);
+        let findings = check_networks(Err(&error), |_| Ok(()));
+        assert_eq!(findings.len(), 2);
+        assert!(findings[0].message.contains("255.255.255.255 is usable"));
+        assert_eq!(findings[1].severity, Severity::Warning);
+        assert!(findings[1].message.starts_with("routes unavailable"));
+    }
+}
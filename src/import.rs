@@ -0,0 +1,86 @@
+pub mod ansible;
+pub mod neigh;
+pub mod nmap;
+pub mod pcap;
+pub mod ssh;
+pub mod text;
+
+use std::net::IpAddr;
+use wake_on_lan::MacAddr;
+
+/// Parses a MAC leniently, as the tools imported from print them.
+pub fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    MacAddr::parse_lenient(text).ok().map(MacAddr::octets)
+}
+
+/// A host observed in an external source, ready to become a wake target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub mac: [u8; 6],
+    pub ip: Option<IpAddr>,
+    pub name: Option<String>,
+}
+
+impl Entry {
+    pub fn new(mac: [u8; 6]) -> Self {
+        Entry {
+            mac,
+            ip: None,
+            name: None,
+        }
+    }
+}
+
+/// Adds `entry` to `entries`, filling in the IP and name of an existing entry
+/// with the same MAC rather than duplicating it.
+pub fn merge(entries: &mut Vec<Entry>, entry: Entry) {
+    match entries.iter_mut().find(|e| e.mac == entry.mac) {
+        Some(existing) => {
+            if existing.ip.is_none() {
+                existing.ip = entry.ip;
+            }
+            if existing.name.is_none() {
+                existing.name = entry.name;
+            }
+        }
+        None => entries.push(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_merge_fills_missing_fields() {
+        let mut entries = vec![Entry::new([1; 6])];
+        merge(
+            &mut entries,
+            Entry {
+                ip: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+                ..Entry::new([1; 6])
+            },
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].ip, Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+    }
+
+    #[test]
+    fn test_merge_keeps_existing_fields() {
+        let mut entries = vec![Entry {
+            name: Some(String::from("nas")),
+            ..Entry::new([1; 6])
+        }];
+        merge(
+            &mut entries,
+            Entry {
+                name: Some(String::from("other")),
+                ..Entry::new([1; 6])
+            },
+        );
+        merge(&mut entries, Entry::new([2; 6]));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name.as_deref(), Some("nas"));
+    }
+}
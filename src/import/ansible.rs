@@ -0,0 +1,257 @@
+use super::{Entry, merge, parse_mac};
+
+/// Extracts hosts carrying a `mac` hostvar from an Ansible inventory, in
+/// either INI or YAML form. `ansible_host` becomes the IP when it is an
+/// address rather than a DNS name.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let hosts = if looks_like_yaml(text) {
+        parse_yaml(text)
+    } else {
+        parse_ini(text)
+    };
+
+    let mut entries = Vec::new();
+    for (name, vars) in hosts {
+        let Some(mac) = var(&vars, "mac") else {
+            continue;
+        };
+        let mac = parse_mac(mac).ok_or_else(|| format!("host {name}: invalid mac '{mac}'"))?;
+        let ip = var(&vars, "ansible_host").and_then(|host| host.parse().ok());
+        merge(
+            &mut entries,
+            Entry {
+                mac,
+                ip,
+                name: Some(name),
+            },
+        );
+    }
+    Ok(entries)
+}
+
+type Host = (String, Vec<(String, String)>);
+
+fn var<'a>(vars: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn looks_like_yaml(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .is_some_and(|line| line == "---" || line.ends_with(':'))
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+fn parse_ini(text: &str) -> Vec<Host> {
+    let mut hosts = Vec::new();
+    let mut in_hosts_section = true;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            // `[group:vars]` and `[group:children]` don't list hosts.
+            in_hosts_section = !section.contains(':');
+            continue;
+        }
+        if !in_hosts_section {
+            continue;
+        }
+        let mut fields = split_ini_fields(line).into_iter();
+        let Some(name) = fields.next() else {
+            continue;
+        };
+        let vars = fields
+            .filter_map(|field| {
+                let (key, value) = field.split_once('=')?;
+                Some((key.trim().to_owned(), unquote(value).to_owned()))
+            })
+            .collect();
+        hosts.push((name, vars));
+    }
+    hosts
+}
+
+/// Splits a host line on whitespace, keeping quoted values together and
+/// stopping at a trailing comment.
+fn split_ini_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (None, '#') => break,
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                field.push(c);
+            }
+            (Some(q), _) if c == q => {
+                quote = None;
+                field.push(c);
+            }
+            (None, c) if c.is_whitespace() => {
+                if !field.is_empty() {
+                    fields.push(std::mem::take(&mut field));
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+/// A deliberately small YAML reader covering the block-mapping layout Ansible
+/// documents for inventories: every key under a `hosts:` mapping is a host,
+/// and the scalar keys beneath it are its variables.
+fn parse_yaml(text: &str) -> Vec<Host> {
+    let mut hosts: Vec<Host> = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    for line in text.lines() {
+        let content = strip_yaml_comment(line);
+        let trimmed = content.trim();
+        if trimmed.is_empty() || trimmed == "---" || trimmed == "..." {
+            continue;
+        }
+        let indent = content.len() - content.trim_start().len();
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let key = unquote(key).to_owned();
+        let value = unquote(value);
+
+        while path.last().is_some_and(|(i, _)| *i >= indent) {
+            path.pop();
+        }
+        let parent = path.last().map(|(_, k)| k.as_str());
+        let grandparent = path.len().checked_sub(2).map(|i| path[i].1.as_str());
+        if parent == Some("hosts") {
+            hosts.push((key.clone(), Vec::new()));
+        } else if grandparent == Some("hosts") && !value.is_empty() {
+            let host = &path[path.len() - 1].1;
+            if let Some((_, vars)) = hosts.iter_mut().rev().find(|(name, _)| name == host) {
+                vars.push((key.clone(), value.to_owned()));
+            }
+        }
+        path.push((indent, key));
+    }
+    hosts
+}
+
+fn strip_yaml_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') if previous.is_whitespace() => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89];
+
+    #[test]
+    fn test_parse_ini() {
+        let inventory = r#"
+# office machines
+[desktops]
+nas ansible_host=192.168.1.10 mac=b8:ae:ed:9c:c7:89
+laptop ansible_host=laptop.lan mac="00:11:22:33:44:55"  # roams
+printer ansible_host=192.168.1.20
+
+[desktops:vars]
+mac=ff:ff:ff:ff:ff:ff
+
+[servers]
+nas
+"#;
+        let entries = parse(inventory).unwrap();
+        assert_eq!(
+            entries,
+            [
+                Entry {
+                    mac: MAC,
+                    ip: Some("192.168.1.10".parse().unwrap()),
+                    name: Some(String::from("nas")),
+                },
+                Entry {
+                    mac: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                    ip: None,
+                    name: Some(String::from("laptop")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let inventory = r#"---
+all:
+  hosts:
+    nas:
+      ansible_host: 192.168.1.10
+      mac: "b8:ae:ed:9c:c7:89"  # onboard NIC
+  children:
+    desktops:
+      hosts:
+        gaming-pc:
+          mac: '00:11:22:33:44:55'
+        printer:
+      vars:
+        mac: ff:ff:ff:ff:ff:ff
+"#;
+        let entries = parse(inventory).unwrap();
+        assert_eq!(
+            entries,
+            [
+                Entry {
+                    mac: MAC,
+                    ip: Some("192.168.1.10".parse().unwrap()),
+                    name: Some(String::from("nas")),
+                },
+                Entry {
+                    mac: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                    ip: None,
+                    name: Some(String::from("gaming-pc")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_mac() {
+        let err = parse("[all]\nnas mac=nope\n").unwrap_err();
+        assert!(err.contains("nas"));
+    }
+
+    #[test]
+    fn test_strip_yaml_comment() {
+        assert_eq!(strip_yaml_comment("mac: '#1' # c"), "mac: '#1' ");
+        assert_eq!(strip_yaml_comment("a#b"), "a#b");
+    }
+}
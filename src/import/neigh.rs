@@ -0,0 +1,78 @@
+use super::{Entry, merge};
+use crate::neighbor;
+
+/// Reads IP/MAC pairs from neighbor-table listings: `ip neigh`,
+/// `/proc/net/arp` and `arp -a`, through the parsers [`neighbor::table`]
+/// uses. Incomplete entries and multicast or broadcast MACs are skipped.
+pub fn parse(listing: &str) -> Vec<Entry> {
+    let neighbors = if listing.starts_with("IP address") {
+        neighbor::parse_proc_arp(listing)
+    } else {
+        neighbor::parse_ip_neigh(listing)
+    };
+    let entries = neighbors
+        .into_iter()
+        .map(|neighbor| Entry {
+            ip: Some(neighbor.ip),
+            ..Entry::new(neighbor.mac)
+        })
+        .chain(
+            neighbor::parse_arp_a(listing)
+                .into_iter()
+                .map(|(neighbor, name)| Entry {
+                    ip: Some(neighbor.ip),
+                    name,
+                    ..Entry::new(neighbor.mac)
+                }),
+        )
+        .filter(|entry| entry.mac[0] & 0x01 == 0);
+    let mut merged = Vec::new();
+    for entry in entries {
+        merge(&mut merged, entry);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neighbor::tests::{ARP_A, IP_NEIGH, PROC_ARP, WINDOWS_ARP_A};
+
+    const MAC: [u8; 6] = [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89];
+
+    fn entry(ip: &str, mac: [u8; 6], name: Option<&str>) -> Entry {
+        Entry {
+            mac,
+            ip: Some(ip.parse().unwrap()),
+            name: name.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_parse_neighbor_tables() {
+        assert_eq!(
+            parse(IP_NEIGH),
+            [
+                entry("192.168.1.40", MAC, None),
+                entry("fe80::1", [0x00, 0x11, 0x22, 0x33, 0x44, 0x55], None),
+            ]
+        );
+        assert_eq!(parse(PROC_ARP), [entry("192.168.1.40", MAC, None)]);
+    }
+
+    #[test]
+    fn test_parse_arp_a() {
+        assert_eq!(
+            parse(ARP_A),
+            [
+                entry("192.168.1.40", MAC, Some("nas.lan")),
+                entry("192.168.1.1", [0x00, 0x11, 0x22, 0x33, 0x44, 0x55], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_windows_arp_a() {
+        assert_eq!(parse(WINDOWS_ARP_A), [entry("192.168.1.40", MAC, None)]);
+    }
+}
@@ -0,0 +1,157 @@
+use super::{Entry, merge, parse_mac};
+use std::net::IpAddr;
+
+#[derive(Default)]
+struct Host {
+    mac: Option<[u8; 6]>,
+    ip: Option<IpAddr>,
+    name: Option<String>,
+}
+
+/// Extracts hosts from nmap's XML output (`-oX`). Only hosts with a MAC
+/// address are returned, which nmap reports for targets on the local segment.
+pub fn parse(xml: &str) -> Result<Vec<Entry>, String> {
+    if !xml.contains("<nmaprun") {
+        return Err(String::from(
+            "not an nmap XML report (no <nmaprun> element)",
+        ));
+    }
+
+    let mut entries = Vec::new();
+    let mut host: Option<Host> = None;
+    let mut in_hosthint = false;
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            return Err(String::from("unterminated XML tag"));
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let name_end = tag
+            .char_indices()
+            .skip(1)
+            .find(|&(_, c)| c.is_whitespace() || c == '/')
+            .map_or(tag.len(), |(i, _)| i);
+        match &tag[..name_end] {
+            "host" => host = Some(Host::default()),
+            "/host" => {
+                if let Some(Host {
+                    mac: Some(mac),
+                    ip,
+                    name,
+                }) = host.take()
+                {
+                    merge(&mut entries, Entry { mac, ip, name });
+                }
+            }
+            "hosthint" => in_hosthint = !tag.ends_with('/'),
+            "/hosthint" => in_hosthint = false,
+            "address" if !in_hosthint => {
+                let Some(host) = &mut host else {
+                    continue;
+                };
+                let addr = attribute(tag, "addr").unwrap_or_default();
+                match attribute(tag, "addrtype").as_deref() {
+                    Some("mac") => host.mac = parse_mac(&addr),
+                    Some("ipv4" | "ipv6") if host.ip.is_none() => host.ip = addr.parse().ok(),
+                    _ => {}
+                }
+            }
+            "hostname" if !in_hosthint => {
+                if let Some(host @ Host { name: None, .. }) = &mut host {
+                    host.name = attribute(tag, "name");
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// Reads an attribute value from the inside of a start tag, decoding the
+/// predefined XML entities.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().next_back();
+        let after = rest[pos + name.len()..].trim_start();
+        rest = &rest[pos + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let value = &value[1..];
+        let end = value.find(quote)?;
+        return Some(
+            value[..end]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&"),
+        );
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nmaprun scanner="nmap" args="nmap -sn -oX - 192.168.1.0/24">
+<hosthint><status state="up"/><address addr="192.168.1.9" addrtype="ipv4"/>
+<address addr="11:22:33:44:55:66" addrtype="mac"/></hosthint>
+<host><status state="up" reason="arp-response"/>
+<address addr="192.168.1.40" addrtype="ipv4"/>
+<address addr="B8:AE:ED:9C:C7:89" addrtype="mac" vendor="Elitegroup"/>
+<hostnames><hostname name="nas.lan" type="PTR"/><hostname name="other" type="user"/></hostnames>
+</host>
+<host><status state="up" reason="localhost-response"/>
+<address addr="192.168.1.2" addrtype="ipv4"/>
+<hostnames></hostnames>
+</host>
+<host><address addrtype='mac' addr='00:11:22:33:44:55'/></host>
+</nmaprun>"#;
+
+    #[test]
+    fn test_parse_report() {
+        let entries = parse(REPORT).unwrap();
+        assert_eq!(
+            entries,
+            [
+                Entry {
+                    mac: [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+                    ip: Some("192.168.1.40".parse().unwrap()),
+                    name: Some(String::from("nas.lan")),
+                },
+                Entry::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_other_xml() {
+        assert!(parse("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_attribute() {
+        let tag = r#"hostname name="a&amp;b" type="PTR""#;
+        assert_eq!(attribute(tag, "name").as_deref(), Some("a&b"));
+        assert_eq!(attribute(tag, "type").as_deref(), Some("PTR"));
+        assert_eq!(attribute(tag, "addr"), None);
+        assert_eq!(
+            attribute(r#"address vendor="x" addrtype="mac""#, "type"),
+            None
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use super::{Entry, merge};
+use crate::decode;
+use std::net::{IpAddr, Ipv4Addr};
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_WOL: u16 = 0x0842;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+
+/// Extracts wake targets from a classic libpcap capture: the MACs carried in
+/// magic packets (raw 0x0842 frames or UDP payloads) and the sender MAC/IP
+/// pairs of ARP traffic.
+pub fn parse(data: &[u8]) -> Result<Vec<Entry>, String> {
+    if data.len() < 24 {
+        return Err(String::from("file is too short to be a pcap capture"));
+    }
+    let magic: [u8; 4] = data[0..4].try_into().unwrap();
+    let big_endian = match magic {
+        [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => true,
+        [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => false,
+        [0x0a, 0x0d, 0x0d, 0x0a] => {
+            return Err(String::from(
+                "pcapng captures are not supported; convert with `editcap -F pcap`",
+            ));
+        }
+        _ => return Err(String::from("not a pcap capture (bad magic number)")),
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes: [u8; 4] = bytes.try_into().unwrap();
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+    let linktype = read_u32(&data[20..24]) & 0x0FFF_FFFF;
+    if ![LINKTYPE_ETHERNET, LINKTYPE_RAW, LINKTYPE_LINUX_SLL].contains(&linktype) {
+        return Err(format!("unsupported link type {linktype}"));
+    }
+
+    let mut entries = Vec::new();
+    let mut offset = 24;
+    while offset + 16 <= data.len() {
+        let captured = read_u32(&data[offset + 8..offset + 12]) as usize;
+        let start = offset + 16;
+        let Some(frame) = data.get(start..start + captured) else {
+            return Err(format!("truncated packet record at offset {offset}"));
+        };
+        match linktype {
+            LINKTYPE_ETHERNET => ethernet(frame, &mut entries),
+            LINKTYPE_LINUX_SLL if frame.len() >= 16 => {
+                let ethertype = u16::from_be_bytes([frame[14], frame[15]]);
+                layer3(ethertype, &frame[16..], &mut entries);
+            }
+            LINKTYPE_RAW => match frame.first().map(|b| b >> 4) {
+                Some(4) => layer3(ETHERTYPE_IPV4, frame, &mut entries),
+                Some(6) => layer3(ETHERTYPE_IPV6, frame, &mut entries),
+                _ => {}
+            },
+            _ => {}
+        }
+        offset = start + captured;
+    }
+    Ok(entries)
+}
+
+fn ethernet(frame: &[u8], entries: &mut Vec<Entry>) {
+    let mut offset = 12;
+    while let Some(bytes) = frame.get(offset..offset + 2) {
+        let ethertype = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if ethertype == ETHERTYPE_VLAN {
+            offset += 4;
+        } else {
+            layer3(ethertype, &frame[offset + 2..], entries);
+            return;
+        }
+    }
+}
+
+fn layer3(ethertype: u16, packet: &[u8], entries: &mut Vec<Entry>) {
+    match ethertype {
+        ETHERTYPE_ARP => arp(packet, entries),
+        ETHERTYPE_WOL => magic_packet(packet, entries),
+        ETHERTYPE_IPV4 if packet.len() >= 20 && packet[9] == 17 => {
+            let header_len = usize::from(packet[0] & 0x0F) * 4;
+            if let Some(udp) = packet.get(header_len..) {
+                udp_payload(udp, entries);
+            }
+        }
+        ETHERTYPE_IPV6 if packet.len() >= 40 && packet[6] == 17 => {
+            udp_payload(&packet[40..], entries);
+        }
+        _ => {}
+    }
+}
+
+fn udp_payload(udp: &[u8], entries: &mut Vec<Entry>) {
+    if let Some(payload) = udp.get(8..) {
+        magic_packet(payload, entries);
+    }
+}
+
+fn magic_packet(payload: &[u8], entries: &mut Vec<Entry>) {
+    let decoded = decode::decode(payload);
+    if let (Some(mac), true) = (decoded.mac, decoded.problems.is_empty()) {
+        merge(entries, Entry::new(mac));
+    }
+}
+
+fn arp(packet: &[u8], entries: &mut Vec<Entry>) {
+    // Only Ethernet/IPv4 ARP: htype 1, ptype 0x0800, hlen 6, plen 4.
+    if packet.len() < 28 || packet[0..6] != [0x00, 0x01, 0x08, 0x00, 6, 4] {
+        return;
+    }
+    let mac: [u8; 6] = packet[8..14].try_into().unwrap();
+    let ip = Ipv4Addr::new(packet[14], packet[15], packet[16], packet[17]);
+    if mac == [0; 6] || mac == [0xFF; 6] || mac[0] & 0x01 != 0 {
+        return;
+    }
+    // ARP probes announce 0.0.0.0 while a host is still checking for conflicts.
+    let ip = (!ip.is_unspecified()).then_some(IpAddr::V4(ip));
+    merge(
+        entries,
+        Entry {
+            ip,
+            ..Entry::new(mac)
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89];
+
+    fn capture(linktype: u32, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&65535u32.to_le_bytes());
+        data.extend_from_slice(&linktype.to_le_bytes());
+        for frame in frames {
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    fn magic() -> Vec<u8> {
+        wake_on_lan::magic_packet(MAC.into()).to_vec()
+    }
+
+    fn ethernet_frame(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xFF; 6];
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn udp_ipv4(payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 17, 0, 0];
+        packet.extend_from_slice(&[192, 168, 1, 2, 255, 255, 255, 255]);
+        packet.extend_from_slice(&[0x30, 0x39, 0, 9, 0, 0, 0, 0]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn arp_reply(mac: [u8; 6], ip: [u8; 4]) -> Vec<u8> {
+        let mut packet = vec![0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x02];
+        packet.extend_from_slice(&mac);
+        packet.extend_from_slice(&ip);
+        packet.extend_from_slice(&[0x02, 0, 0, 0, 0, 1, 192, 168, 1, 2]);
+        packet
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(parse(&[0; 10]).is_err());
+        assert!(parse(&[0; 24]).is_err());
+    }
+
+    #[test]
+    fn test_parse_udp_magic_packet() {
+        let frame = ethernet_frame(ETHERTYPE_IPV4, &udp_ipv4(&magic()));
+        let entries = parse(&capture(LINKTYPE_ETHERNET, &[frame])).unwrap();
+        assert_eq!(entries, [Entry::new(MAC)]);
+    }
+
+    #[test]
+    fn test_parse_raw_wol_frame() {
+        let frame = ethernet_frame(ETHERTYPE_WOL, &magic());
+        let entries = parse(&capture(LINKTYPE_ETHERNET, &[frame])).unwrap();
+        assert_eq!(entries, [Entry::new(MAC)]);
+    }
+
+    #[test]
+    fn test_parse_raw_ip_link_type() {
+        let entries = parse(&capture(LINKTYPE_RAW, &[udp_ipv4(&magic())])).unwrap();
+        assert_eq!(entries, [Entry::new(MAC)]);
+    }
+
+    #[test]
+    fn test_parse_arp_merges_with_magic_packet() {
+        let frames = [
+            ethernet_frame(ETHERTYPE_WOL, &magic()),
+            ethernet_frame(ETHERTYPE_ARP, &arp_reply(MAC, [192, 168, 1, 40])),
+        ];
+        let entries = parse(&capture(LINKTYPE_ETHERNET, &frames)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].ip,
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 40)))
+        );
+    }
+
+    #[test]
+    fn test_parse_arp_probe_has_no_ip() {
+        let frame = ethernet_frame(ETHERTYPE_ARP, &arp_reply(MAC, [0, 0, 0, 0]));
+        let entries = parse(&capture(LINKTYPE_ETHERNET, &[frame])).unwrap();
+        assert_eq!(entries, [Entry::new(MAC)]);
+    }
+
+    #[test]
+    fn test_parse_truncated_record() {
+        let mut data = capture(
+            LINKTYPE_ETHERNET,
+            &[ethernet_frame(ETHERTYPE_WOL, &magic())],
+        );
+        data.truncate(data.len() - 10);
+        assert!(parse(&data).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use super::{Entry, merge, parse_mac};
+
+/// The comment that gives a `Host` block its MAC address, since OpenSSH has
+/// no keyword for it.
+const MAC_COMMENT: &str = "wol-mac:";
+
+/// Extracts hosts from an OpenSSH client config. A block is imported when it
+/// carries a `#wol-mac: <MAC>` comment; its first non-pattern alias becomes
+/// the name, and `HostName` becomes the IP when it is an address.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut block: Option<Block> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('#') {
+            if let (Some(block), Some(mac)) = (
+                block.as_mut(),
+                comment.trim_start().strip_prefix(MAC_COMMENT),
+            ) {
+                block.mac = Some(mac.trim().to_owned());
+            }
+            continue;
+        }
+        let Some((keyword, value)) = split_keyword(line) else {
+            continue;
+        };
+        if keyword.eq_ignore_ascii_case("host") || keyword.eq_ignore_ascii_case("match") {
+            if let Some(block) = block.take() {
+                block.finish(&mut entries)?;
+            }
+            if keyword.eq_ignore_ascii_case("host") {
+                block = Some(Block::new(value));
+            }
+        } else if keyword.eq_ignore_ascii_case("hostname")
+            && let Some(block) = block.as_mut()
+        {
+            block.hostname = Some(value.to_owned());
+        }
+    }
+    if let Some(block) = block {
+        block.finish(&mut entries)?;
+    }
+    Ok(entries)
+}
+
+struct Block {
+    alias: Option<String>,
+    hostname: Option<String>,
+    mac: Option<String>,
+}
+
+impl Block {
+    fn new(patterns: &str) -> Self {
+        let alias = patterns
+            .split_whitespace()
+            .find(|p| !p.contains(['*', '?', '!']))
+            .map(str::to_owned);
+        Block {
+            alias,
+            hostname: None,
+            mac: None,
+        }
+    }
+
+    fn finish(self, entries: &mut Vec<Entry>) -> Result<(), String> {
+        let (Some(name), Some(mac)) = (self.alias, self.mac) else {
+            return Ok(());
+        };
+        let mac = parse_mac(&mac).ok_or_else(|| format!("host {name}: invalid mac '{mac}'"))?;
+        let ip = self.hostname.and_then(|host| host.parse().ok());
+        merge(
+            entries,
+            Entry {
+                mac,
+                ip,
+                name: Some(name),
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Splits a config line into its keyword and argument, which OpenSSH allows
+/// to be separated by whitespace or a single `=`.
+fn split_keyword(line: &str) -> Option<(&str, &str)> {
+    let end = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim();
+    let value = rest
+        .strip_prefix('"')
+        .and_then(|r| r.strip_suffix('"'))
+        .unwrap_or(rest);
+    Some((keyword, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = "\
+Host *
+    ServerAliveInterval 30
+
+Host nas nas.lan
+    HostName 192.168.1.10
+    # wol-mac: b8:ae:ed:9c:c7:89
+
+Host gaming-pc
+    hostname=gaming.lan
+    #wol-mac: 00-11-22-33-44-55
+
+Host printer
+    HostName 192.168.1.20
+
+Match host *.example.com
+    #wol-mac: ff:ff:ff:ff:ff:ff
+";
+        assert_eq!(
+            parse(config).unwrap(),
+            [
+                Entry {
+                    mac: [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+                    ip: Some("192.168.1.10".parse().unwrap()),
+                    name: Some(String::from("nas")),
+                },
+                Entry {
+                    mac: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                    ip: None,
+                    name: Some(String::from("gaming-pc")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_mac() {
+        let err = parse("Host nas\n  #wol-mac: nope\n").unwrap_err();
+        assert!(err.contains("nas"));
+    }
+
+    #[test]
+    fn test_split_keyword() {
+        assert_eq!(
+            split_keyword("HostName 10.0.0.1"),
+            Some(("HostName", "10.0.0.1"))
+        );
+        assert_eq!(split_keyword("User = \"me\""), Some(("User", "me")));
+        assert_eq!(split_keyword("Include"), None);
+    }
+}
@@ -0,0 +1,108 @@
+use super::{Entry, merge};
+use std::net::Ipv4Addr;
+use wake_on_lan::MacAddr;
+
+/// Parses the dotted form Cisco uses, `b8ae.ed9c.c789`.
+pub fn parse_dotted_mac(text: &str) -> Option<[u8; 6]> {
+    let groups: Vec<&str> = text.split('.').collect();
+    if groups.len() != 3
+        || !groups
+            .iter()
+            .all(|g| g.len() == 4 && g.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return None;
+    }
+    let mut mac = [0; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        let group = groups[i / 2];
+        *byte = u8::from_str_radix(&group[i % 2 * 2..][..2], 16).ok()?;
+    }
+    Some(mac)
+}
+
+/// Finds every MAC address in free text, such as a copied router page or
+/// `ipconfig /all` output, in order of appearance: six two-digit octets with
+/// one separator throughout, or the dotted form. The all-zero placeholder
+/// of incomplete entries is skipped.
+pub fn find_macs(text: &str) -> Vec<[u8; 6]> {
+    mac_tokens(text)
+        .filter_map(|token| {
+            token
+                .parse::<MacAddr>()
+                .ok()
+                .map(MacAddr::octets)
+                .or_else(|| parse_dotted_mac(token))
+        })
+        .filter(|mac| *mac != [0; 6])
+        .collect()
+}
+
+/// Splits free text into the runs of hex digits and separators that could
+/// be a MAC address.
+pub fn mac_tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_ascii_hexdigit() || matches!(c, ':' | '-' | '.')))
+        .map(|token| token.trim_matches(['.', ':', '-']))
+}
+
+/// Reads hosts out of free text: every MAC address found, with the first
+/// IPv4 address on the same line, if any.
+pub fn parse(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for line in text.lines() {
+        let ip = line
+            .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .find_map(|token| token.trim_matches('.').parse::<Ipv4Addr>().ok());
+        for mac in find_macs(line) {
+            merge(
+                &mut entries,
+                Entry {
+                    ip: ip.map(Into::into),
+                    ..Entry::new(mac)
+                },
+            );
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_macs() {
+        let text = "\
+Ethernet adapter Ethernet:
+   Physical Address. . . . . . . . . : B8-AE-ED-9C-C7-89
+   Link-local IPv6 Address . . . . . : fe80::1c2b:3a4d:5e6f:7081%12(Preferred)
+switch# show mac address-table: 0011.2233.4455 DYNAMIC Gi1/0/3
+incomplete 00:00:00:00:00:00, last 02:42:ac:11:00:02.
+";
+        assert_eq!(
+            find_macs(text),
+            [
+                [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+                [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                [0x02, 0x42, 0xac, 0x11, 0x00, 0x02],
+            ]
+        );
+        assert!(find_macs("12:30 on 2024-06-01, build abcd.ef01").is_empty());
+        assert!(find_macs("version 1-2-3-4-5-6 or 0:1:2:3:4:5").is_empty());
+        assert!(find_macs("00:11-22:33:44:55").is_empty());
+    }
+
+    #[test]
+    fn test_parse_takes_ip_from_the_line() {
+        let text = "desktop  192.168.1.40  b8:ae:ed:9c:c7:89  online\nlaptop  00:11:22:33:44:55\n";
+        assert_eq!(
+            parse(text),
+            [
+                Entry {
+                    ip: Some("192.168.1.40".parse().unwrap()),
+                    ..Entry::new([0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89])
+                },
+                Entry::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::route;
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+/// How long a TCP knock waits; the SYN is what counts, not the answer.
+const TCP_KNOCK_TIMEOUT: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// One step of a port-knocking sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Knock {
+    pub port: u16,
+    pub protocol: Protocol,
+}
+
+/// Parses one step of a sequence, `PORT[/tcp|/udp]`. Ports without a
+/// protocol are knocked over TCP.
+pub fn parse_knock(step: &str) -> Result<Knock, String> {
+    let step = step.trim();
+    let (port, protocol) = match step.split_once('/') {
+        Some((port, "tcp")) => (port, Protocol::Tcp),
+        Some((port, "udp")) => (port, Protocol::Udp),
+        Some(_) => return Err(format!("invalid knock '{step}' (expected PORT[/tcp|/udp])")),
+        None => (step, Protocol::Tcp),
+    };
+    let port = port
+        .parse()
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(|| format!("invalid knock port '{port}'"))?;
+    Ok(Knock { port, protocol })
+}
+
+/// Checks that `ip` is a single host to knock on, not a multicast group or
+/// a broadcast address (limited, or directed to one of the attached networks
+/// in `routes`), where the knocks would reach every host.
+pub fn check_target(ip: IpAddr, routes: &[route::Route]) -> Result<(), String> {
+    let broadcast = match ip {
+        IpAddr::V4(ip) => {
+            ip.is_broadcast()
+                || routes.iter().any(|route| {
+                    route.gateway.is_none() && route.prefix_len() < 31 && route.broadcast() == ip
+                })
+        }
+        IpAddr::V6(_) => false,
+    };
+    if ip.is_multicast() || broadcast {
+        return Err(format!(
+            "--knock would knock on every host reached through {ip}; give --address the target's own address"
+        ));
+    }
+    Ok(())
+}
+
+/// Knocks on each port of `sequence` at `ip` in turn, waiting `delay` after
+/// each. Closed ports are the point of knocking, so failures are ignored
+/// unless the local socket can't be set up at all.
+pub fn send(ip: IpAddr, sequence: &[Knock], delay: Duration) -> Result<(), String> {
+    let bind = if ip.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket =
+        UdpSocket::bind(bind).map_err(|e| format!("failed to bind socket to {bind}: {e}"))?;
+    for knock in sequence {
+        let destination = SocketAddr::new(ip, knock.port);
+        match knock.protocol {
+            Protocol::Tcp => {
+                let _ = TcpStream::connect_timeout(&destination, TCP_KNOCK_TIMEOUT);
+            }
+            Protocol::Udp => {
+                let _ = socket.send_to(&[], destination);
+            }
+        }
+        thread::sleep(delay);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use route::tests::network;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_knock() {
+        assert_eq!(
+            parse_knock("7000"),
+            Ok(Knock {
+                port: 7000,
+                protocol: Protocol::Tcp,
+            })
+        );
+        assert_eq!(
+            parse_knock("8000/udp"),
+            Ok(Knock {
+                port: 8000,
+                protocol: Protocol::Udp,
+            })
+        );
+        assert!(parse_knock("7000/icmp").is_err());
+        assert!(parse_knock("0").is_err());
+        assert!(parse_knock("").is_err());
+    }
+
+    #[test]
+    fn test_check_target() {
+        let routes = [
+            network([0, 0, 0, 0], [0, 0, 0, 0], Some([192, 168, 1, 1])),
+            network([192, 168, 1, 0], [255, 255, 255, 0], None),
+        ];
+        let check = |ip: &str| check_target(ip.parse().unwrap(), &routes);
+        assert!(check("192.168.1.40").is_ok());
+        assert!(check("203.0.113.7").is_ok());
+        assert!(check("2001:db8::7").is_ok());
+        assert!(check("255.255.255.255").is_err());
+        assert!(check("192.168.1.255").is_err());
+        assert!(check("239.255.255.250").is_err());
+        assert!(check("ff02::1").is_err());
+    }
+
+    #[test]
+    fn test_send_reaches_each_port_in_order() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        udp.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let sequence = [
+            Knock {
+                port: udp.local_addr().unwrap().port(),
+                protocol: Protocol::Udp,
+            },
+            Knock {
+                port: tcp.local_addr().unwrap().port(),
+                protocol: Protocol::Tcp,
+            },
+        ];
+        send("127.0.0.1".parse().unwrap(), &sequence, Duration::ZERO).unwrap();
+        assert_eq!(udp.recv(&mut [0; 8]).unwrap(), 0);
+        tcp.set_nonblocking(true).unwrap();
+        assert!(tcp.accept().is_ok());
+    }
+}
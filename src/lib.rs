@@ -0,0 +1,639 @@
+//! Sending Wake-on-LAN magic packets from Rust.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use wake_on_lan::WakeRequest;
+//!
+//! let report = WakeRequest::builder()
+//!     .mac("b8:ae:ed:9c:c7:89")
+//!     .broadcast([192, 168, 1, 255])
+//!     .port(9)
+//!     .retries(3)
+//!     .delay(Duration::from_secs(45 * 60))
+//!     .build()?
+//!     .send()?;
+//! println!("sent {} packets to {}", report.attempts, report.destination);
+//! # Ok::<(), wake_on_lan::Error>(())
+//! ```
+
+pub mod backoff;
+pub mod transport;
+
+use backoff::{Backoff, Strategy};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use transport::{Transport, UdpTransport};
+
+/// The gap between repeated sends of the same request, unless the builder
+/// is given a [`Backoff`].
+const RETRY_BACKOFF: Backoff = Backoff {
+    strategy: Strategy::Fixed,
+    initial: Duration::from_millis(100),
+    max: Duration::from_millis(100),
+};
+
+/// Everything that can go wrong building or sending a [`WakeRequest`].
+#[derive(Debug)]
+pub enum Error {
+    /// No MAC address was given to the builder.
+    MissingMac,
+    /// The MAC address is not six hex octets separated by `:` or `-`.
+    InvalidMac(String),
+    /// A SecureOn password must be 4 or 6 bytes; holds the length given.
+    InvalidSecureOn(usize),
+    /// The socket could not be set up or the send failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingMac => write!(f, "no MAC address given"),
+            Error::InvalidMac(mac) => write!(f, "invalid MAC address '{mac}'"),
+            Error::InvalidSecureOn(len) => {
+                write!(f, "SecureOn password must be 4 or 6 bytes, got {len}")
+            }
+            Error::Io(e) => write!(f, "failed to send packet: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// The lenient MAC parser behind [`MacAddr::parse_lenient`].
+pub(crate) fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut parts = text.trim().split([':', '-']);
+    for byte in &mut mac {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 2 || !part.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        *byte = u8::from_str_radix(part, 16).ok()?;
+    }
+    parts.next().is_none().then_some(mac)
+}
+
+/// Parses a SecureOn password: 6 bytes written like a MAC address, or 4
+/// bytes in dotted decimal like an IPv4 address.
+pub fn parse_secure_on(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    match text.parse::<Ipv4Addr>() {
+        Ok(ip) => Some(ip.octets().to_vec()),
+        Err(_) => parse_mac(text).map(|bytes| bytes.to_vec()),
+    }
+}
+
+/// A MAC address. Parses from six two-digit hex octets, all separated by `:`
+/// or all by `-`, and displays in lowercase with colons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr([u8; 6]);
+
+impl MacAddr {
+    pub const fn new(octets: [u8; 6]) -> Self {
+        MacAddr(octets)
+    }
+
+    pub const fn octets(self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Parses a MAC address as other tools print them: six octets of one or
+    /// two hex digits (macOS drops leading zeros), separated by `:` or `-`.
+    /// [`FromStr`] parses the strict form.
+    pub fn parse_lenient(text: &str) -> Result<Self, Error> {
+        parse_mac(text)
+            .map(MacAddr)
+            .ok_or_else(|| Error::InvalidMac(text.to_string()))
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> Self {
+        MacAddr(octets)
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidMac(text.to_string());
+        let separator = if text.contains('-') { '-' } else { ':' };
+        let mut mac = [0; 6];
+        let mut parts = text.split(separator);
+        for byte in &mut mac {
+            let part = parts.next().ok_or_else(invalid)?;
+            if part.len() != 2 || !part.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+        }
+        match parts.next() {
+            Some(_) => Err(invalid()),
+            None => Ok(MacAddr(mac)),
+        }
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+/// Builds a magic packet: six `0xFF` bytes, then the MAC repeated 16 times.
+/// Usable in const contexts, and never allocates.
+pub const fn magic_packet(mac: MacAddr) -> [u8; 102] {
+    let mut packet = [0xFF; 102];
+    let mut i = 6;
+    while i < 102 {
+        packet[i] = mac.0[(i - 6) % 6];
+        i += 1;
+    }
+    packet
+}
+
+/// Builds a magic packet followed by a 6-byte SecureOn password.
+pub const fn secure_on_packet(mac: MacAddr, password: [u8; 6]) -> [u8; 108] {
+    with_password(mac, &password)
+}
+
+/// Builds a magic packet followed by a 4-byte SecureOn password.
+pub const fn secure_on_packet_4(mac: MacAddr, password: [u8; 4]) -> [u8; 106] {
+    with_password(mac, &password)
+}
+
+/// Copies a magic packet for `mac` into `N` bytes, then `password` after it.
+const fn with_password<const N: usize>(mac: MacAddr, password: &[u8]) -> [u8; N] {
+    let packet = magic_packet(mac);
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < 102 {
+        out[i] = packet[i];
+        i += 1;
+    }
+    while i < N {
+        out[i] = password[i - 102];
+        i += 1;
+    }
+    out
+}
+
+/// The bytes a [`WakeRequest`] sends: a magic packet, alone or with a 4- or
+/// 6-byte SecureOn password. Derefs to a byte slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Payload {
+    Magic([u8; 102]),
+    SecureOn4([u8; 106]),
+    SecureOn6([u8; 108]),
+}
+
+impl Payload {
+    /// Builds the payload for `mac`, with an optional SecureOn password.
+    pub fn new(mac: MacAddr, secure_on: Option<&[u8]>) -> Result<Self, Error> {
+        let Some(password) = secure_on else {
+            return Ok(Payload::Magic(magic_packet(mac)));
+        };
+        if let Ok(password) = password.try_into() {
+            Ok(Payload::SecureOn4(secure_on_packet_4(mac, password)))
+        } else if let Ok(password) = password.try_into() {
+            Ok(Payload::SecureOn6(secure_on_packet(mac, password)))
+        } else {
+            Err(Error::InvalidSecureOn(password.len()))
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Payload::Magic(bytes) => bytes,
+            Payload::SecureOn4(bytes) => bytes,
+            Payload::SecureOn6(bytes) => bytes,
+        }
+    }
+}
+
+impl std::ops::Deref for Payload {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for Payload {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// A validated wake-up, ready to be sent over UDP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeRequest {
+    mac: MacAddr,
+    destination: SocketAddr,
+    payload: Payload,
+    retries: u32,
+    backoff: Backoff,
+    start: Option<Start>,
+}
+
+/// When a [`WakeRequest`] is sent, if not right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Start {
+    At(SystemTime),
+    After(Duration),
+}
+
+/// What was sent for a [`WakeRequest`]. With the `serde` feature it
+/// serializes with the field names below.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WakeReport {
+    /// The local address the packets were sent from.
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    /// How many packets were sent, including the first.
+    pub attempts: u32,
+    /// The size of each packet.
+    pub bytes: usize,
+}
+
+impl WakeRequest {
+    pub fn builder() -> WakeRequestBuilder {
+        WakeRequestBuilder::default()
+    }
+
+    pub fn mac(&self) -> MacAddr {
+        self.mac
+    }
+
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    /// The payload this request sends.
+    pub fn packet(&self) -> Payload {
+        self.payload
+    }
+
+    /// How long [`send`](Self::send) waits before the first packet when
+    /// called at `now`: the builder's delay, the time left until its `at`
+    /// time (nothing once that has passed), or nothing.
+    pub fn wait(&self, now: SystemTime) -> Duration {
+        match self.start {
+            Some(Start::At(time)) => time.duration_since(now).unwrap_or_default(),
+            Some(Start::After(delay)) => delay,
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Waits as [`wait`](Self::wait) says, sends the packet, then `retries`
+    /// more copies, waiting as the builder's backoff says (100 ms by
+    /// default) between them.
+    pub fn send(&self) -> Result<WakeReport, Error> {
+        self.send_with(&mut UdpTransport::new())
+    }
+
+    /// Like [`send`](Self::send), but through `transport` rather than the
+    /// system's UDP sockets.
+    pub fn send_with(&self, transport: &mut impl Transport) -> Result<WakeReport, Error> {
+        thread::sleep(self.wait(SystemTime::now()));
+        let packet = self.payload.as_bytes();
+        let mut source = transport.send_udp(self.destination, packet)?;
+        for attempt in 0..self.retries {
+            thread::sleep(self.backoff.delay(attempt));
+            source = transport.send_udp(self.destination, packet)?;
+        }
+        Ok(WakeReport {
+            source,
+            destination: self.destination,
+            attempts: self.retries + 1,
+            bytes: packet.len(),
+        })
+    }
+}
+
+/// Collects the parts of a [`WakeRequest`]; see [`WakeRequest::builder`].
+/// Defaults to the limited broadcast address, port 9, no retries and sending
+/// right away.
+#[derive(Debug, Clone)]
+pub struct WakeRequestBuilder {
+    mac: Option<String>,
+    broadcast: IpAddr,
+    port: u16,
+    secure_on: Option<Vec<u8>>,
+    retries: u32,
+    backoff: Backoff,
+    start: Option<Start>,
+}
+
+impl Default for WakeRequestBuilder {
+    fn default() -> Self {
+        WakeRequestBuilder {
+            mac: None,
+            broadcast: IpAddr::V4(Ipv4Addr::BROADCAST),
+            port: 9,
+            secure_on: None,
+            retries: 0,
+            backoff: RETRY_BACKOFF,
+            start: None,
+        }
+    }
+}
+
+impl WakeRequestBuilder {
+    /// The MAC address of the device to wake, as `b8:ae:ed:9c:c7:89` or
+    /// `b8-ae-ed-9c-c7-89`.
+    pub fn mac(mut self, mac: impl Into<String>) -> Self {
+        self.mac = Some(mac.into());
+        self
+    }
+
+    /// The address to send to, usually the broadcast address of the target's
+    /// network.
+    pub fn broadcast(mut self, address: impl Into<IpAddr>) -> Self {
+        self.broadcast = address.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// A 4- or 6-byte SecureOn password to append to the packet.
+    pub fn secure_on(mut self, password: impl Into<Vec<u8>>) -> Self {
+        self.secure_on = Some(password.into());
+        self
+    }
+
+    /// How many extra copies to send after the first.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// How long to wait between the retries, instead of a fixed 100 ms.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sends no earlier than `time`, replacing any [`delay`](Self::delay).
+    pub fn at(mut self, time: SystemTime) -> Self {
+        self.start = Some(Start::At(time));
+        self
+    }
+
+    /// Waits this long before sending, counted from each call to
+    /// [`WakeRequest::send`], replacing any [`at`](Self::at) time.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.start = Some(Start::After(delay));
+        self
+    }
+
+    pub fn build(self) -> Result<WakeRequest, Error> {
+        let mac: MacAddr = self.mac.ok_or(Error::MissingMac)?.parse()?;
+        Ok(WakeRequest {
+            mac,
+            destination: SocketAddr::new(self.broadcast, self.port),
+            payload: Payload::new(mac, self.secure_on.as_deref())?,
+            retries: self.retries,
+            backoff: self.backoff,
+            start: self.start,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    #[test]
+    fn test_parse_mac() {
+        assert_eq!(
+            parse_mac("B8:ae:ed:9c:c7:89"),
+            Some([0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89])
+        );
+        assert_eq!(parse_mac("0-1-2-3-4-5"), Some([0, 1, 2, 3, 4, 5]));
+        assert_eq!(parse_mac("00:11:22:33:44"), None);
+        assert_eq!(parse_mac("00:11:22:33:44:55:66"), None);
+        assert_eq!(parse_mac("00:11:22:33:44:zz"), None);
+        assert_eq!(parse_mac("00:11:22:33:44:+5"), None);
+    }
+
+    #[test]
+    fn test_parse_secure_on() {
+        assert_eq!(parse_secure_on("192.168.1.1\n"), Some(vec![192, 168, 1, 1]));
+        assert_eq!(
+            parse_secure_on("01-02-03-04-05-0a"),
+            Some(vec![1, 2, 3, 4, 5, 10])
+        );
+        assert_eq!(parse_secure_on("01:02:03"), None);
+        assert_eq!(parse_secure_on("secret"), None);
+    }
+
+    #[test]
+    fn test_mac_addr() {
+        let mac: MacAddr = "B8-AE-ED-9C-C7-89".parse().unwrap();
+        assert_eq!(mac.octets(), [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89]);
+        assert_eq!(mac.to_string(), "b8:ae:ed:9c:c7:89");
+        assert!(matches!(
+            "nope".parse::<MacAddr>(),
+            Err(Error::InvalidMac(_))
+        ));
+        assert!("0:1:2:3:4:5".parse::<MacAddr>().is_err());
+        assert!("00:11-22:33:44:55".parse::<MacAddr>().is_err());
+        assert!("00:11:22:33:44:55:".parse::<MacAddr>().is_err());
+
+        let lenient = MacAddr::parse_lenient("0:1:2:3:4:5").unwrap();
+        assert_eq!(lenient.octets(), [0, 1, 2, 3, 4, 5]);
+        assert!(matches!(
+            MacAddr::parse_lenient("0:1:2:3:4"),
+            Err(Error::InvalidMac(_))
+        ));
+    }
+
+    #[test]
+    fn test_magic_packet() {
+        const MAC: MacAddr = MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        const PACKET: [u8; 102] = magic_packet(MAC);
+        assert_eq!(&PACKET[..6], &[0xFF; 6]);
+        assert!(PACKET[6..].chunks(6).all(|chunk| chunk == MAC.octets()));
+
+        let packet = secure_on_packet(MAC, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(&packet[..102], &PACKET);
+        assert_eq!(&packet[102..], &[1, 2, 3, 4, 5, 6]);
+
+        let packet = secure_on_packet_4(MAC, [192, 168, 1, 1]);
+        assert_eq!(&packet[..102], &PACKET);
+        assert_eq!(&packet[102..], &[192, 168, 1, 1]);
+    }
+
+    #[test]
+    fn test_builder_defaults_and_validation() {
+        let request = WakeRequest::builder()
+            .mac("00:11:22:33:44:55")
+            .build()
+            .unwrap();
+        assert_eq!(request.destination(), "255.255.255.255:9".parse().unwrap());
+        assert_eq!(
+            request.packet(),
+            Payload::Magic(magic_packet(request.mac()))
+        );
+
+        let request = WakeRequest::builder()
+            .mac("00:11:22:33:44:55")
+            .secure_on([192, 168, 1, 1])
+            .build()
+            .unwrap();
+        assert_eq!(request.packet().len(), 106);
+        assert_eq!(&request.packet()[102..], &[192, 168, 1, 1]);
+
+        assert!(matches!(
+            WakeRequest::builder().build(),
+            Err(Error::MissingMac)
+        ));
+        assert!(matches!(
+            WakeRequest::builder().mac("nope").build(),
+            Err(Error::InvalidMac(mac)) if mac == "nope"
+        ));
+        assert!(matches!(
+            WakeRequest::builder()
+                .mac("00:11:22:33:44:55")
+                .secure_on([1, 2, 3])
+                .build(),
+            Err(Error::InvalidSecureOn(3))
+        ));
+    }
+
+    #[test]
+    fn test_send_with_retries() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = receiver.local_addr().unwrap();
+        let report = WakeRequest::builder()
+            .mac("00:11:22:33:44:55")
+            .broadcast(addr.ip())
+            .port(addr.port())
+            .secure_on(vec![1, 2, 3, 4, 5, 6])
+            .retries(2)
+            .build()
+            .unwrap()
+            .send()
+            .unwrap();
+        assert_eq!(report.attempts, 3);
+        assert_eq!(report.bytes, 108);
+        assert_eq!(report.destination, addr);
+
+        let mut buf = [0; 128];
+        for _ in 0..3 {
+            let (len, from) = receiver.recv_from(&mut buf).unwrap();
+            assert_eq!(len, 108);
+            assert_eq!(from.port(), report.source.port());
+        }
+    }
+
+    #[test]
+    fn test_send_with_mock_transport() {
+        let request = WakeRequest::builder()
+            .mac("00:11:22:33:44:55")
+            .broadcast([192, 168, 1, 255])
+            .retries(1)
+            .build()
+            .unwrap();
+        let mut transport = transport::MockTransport::new();
+        let report = request.send_with(&mut transport).unwrap();
+        assert_eq!(report.source.port(), transport::MOCK_SOURCE_PORT);
+        assert_eq!(report.attempts, 2);
+        let sent = transport::Packet::Udp {
+            destination: "192.168.1.255:9".parse().unwrap(),
+            payload: request.packet().to_vec(),
+        };
+        assert_eq!(transport.sent(), [sent.clone(), sent]);
+
+        let mut transport = transport::MockTransport::new();
+        transport.fail_next(io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            request.send_with(&mut transport),
+            Err(Error::Io(_))
+        ));
+        assert!(transport.sent().is_empty());
+    }
+
+    #[test]
+    fn test_send_with_backoff() {
+        let request = WakeRequest::builder()
+            .mac("00:11:22:33:44:55")
+            .retries(2)
+            .backoff(Backoff {
+                strategy: Strategy::Exponential,
+                initial: Duration::from_millis(20),
+                max: Duration::from_millis(30),
+            })
+            .build()
+            .unwrap();
+        let mut transport = transport::MockTransport::new();
+        let start = std::time::Instant::now();
+        assert_eq!(request.send_with(&mut transport).unwrap().attempts, 3);
+        // 20 ms, then 40 ms capped at 30 ms.
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(transport.sent().len(), 3);
+    }
+
+    #[test]
+    fn test_scheduled_send() {
+        let builder = WakeRequest::builder().mac("00:11:22:33:44:55");
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_717_225_200);
+        let minute = Duration::from_secs(60);
+        assert_eq!(builder.clone().build().unwrap().wait(now), Duration::ZERO);
+        let at = builder.clone().at(now + minute).build().unwrap();
+        assert_eq!(at.wait(now), minute);
+        assert_eq!(at.wait(now + 2 * minute), Duration::ZERO);
+        let delayed = builder.clone().at(now).delay(minute).build().unwrap();
+        assert_eq!(delayed.wait(now + 2 * minute), minute);
+
+        let request = builder.delay(Duration::from_millis(30)).build().unwrap();
+        let mut transport = transport::MockTransport::new();
+        let start = std::time::Instant::now();
+        request.send_with(&mut transport).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert_eq!(transport.sent().len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_report_serde_round_trip() {
+        let report = WakeReport {
+            source: "192.168.1.20:50000".parse().unwrap(),
+            destination: "192.168.1.255:9".parse().unwrap(),
+            attempts: 2,
+            bytes: 102,
+        };
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            json,
+            r#"{"source":"192.168.1.20:50000","destination":"192.168.1.255:9","attempts":2,"bytes":102}"#
+        );
+        assert_eq!(serde_json::from_str::<WakeReport>(&json).unwrap(), report);
+    }
+}
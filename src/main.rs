@@ -1,55 +1,1912 @@
-use clap::Parser;
-use std::net::UdpSocket;
+mod build_info;
+mod capture;
+mod clipboard;
+mod decode;
+mod dns;
+mod doctor;
+mod import;
+mod knock;
+mod neighbor;
+mod netbios;
+mod probe;
+mod remote;
+mod resolve;
+mod route;
+mod routeros;
+mod schedule;
+mod snmp;
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use wake_on_lan::backoff;
+use wake_on_lan::transport::Transport;
+use wake_on_lan::{MacAddr, Payload};
 
 /// Program to send Wake-on-LAN packets
+///
+/// Running without a subcommand is the same as `wake`, so `wake-on-lan -m <MAC>`
+/// keeps working.
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about, disable_version_flag = true)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    wake: WakeArgs,
+
+    #[command(flatten)]
+    schedule: ScheduleArgs,
+
+    /// When to color the output; `auto` honors NO_COLOR and only colors terminals
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Give up and exit with status 124 if the command hasn't finished after
+    /// this many seconds, covering name resolution, sending and probing
+    #[arg(long, global = true, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: Option<u64>,
+
+    /// Print version
+    #[arg(short = 'V', long, global = true)]
+    version: bool,
+
+    /// The format for --version; `json` adds the git commit, build date,
+    /// target and features
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "FORMAT",
+        requires = "version"
+    )]
+    output: Option<OutputFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+struct WakeArgs {
     /// The MAC address of the device to wake up
-    #[arg(short, long, value_parser = validate_mac)]
-    mac: String,
+    #[arg(short, long, required_unless_present_any = ["clipboard", "version"], value_parser = validate_mac)]
+    mac: Option<MacAddr>,
+
+    /// Take the MAC address from the first one found on the clipboard
+    #[arg(long, conflicts_with = "mac")]
+    clipboard: bool,
 
-    /// The broadcast address to send the packet to
+    /// The broadcast address to send the packet to. Names the system resolver
+    /// doesn't know fall back to mDNS (`.local`) or LLMNR and NetBIOS
     #[arg(short, long, default_value = "255.255.255.255")]
     address: String,
 
-    /// The UDP port to send the packet to
+    /// The port to send the packet to
     #[arg(short, long, default_value_t = 9)]
     port: u16,
+
+    #[command(flatten)]
+    resolve: ResolveArgs,
+
+    #[command(flatten)]
+    backoff: BackoffArgs,
+
+    /// The TTL of packets sent to an IPv4 multicast group, i.e. how many
+    /// routers they may cross
+    #[arg(long, value_name = "TTL", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=255))]
+    multicast_ttl: u32,
+
+    /// Send multicast out of the interface that has this local IPv4 address
+    #[arg(long, value_name = "ADDRESS")]
+    multicast_interface: Option<Ipv4Addr>,
+
+    /// When the address is a host on a directly attached network, send to
+    /// that network's broadcast address instead, picked from the routing
+    /// table, so a sleeping host that no longer answers ARP still gets it
+    #[arg(long, conflicts_with = "routeros")]
+    subnet_broadcast: bool,
+
+    /// Send UDP from this local address, for hosts with several addresses
+    /// when the target's firewall expects one of them
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["multicast_interface", "routeros"])]
+    source: Option<IpAddr>,
+
+    /// After each UDP send to a host or a remote network's broadcast
+    /// address, wait briefly for a router's ICMP unreachable and fail if one
+    /// arrives
+    #[arg(long, conflicts_with = "routeros")]
+    watch_unreachable: bool,
+
+    /// Send to every address the host resolves to, alternating families,
+    /// instead of only the first
+    #[arg(long)]
+    all_addresses: bool,
+
+    /// The transport used to deliver the packet
+    #[arg(short, long, value_enum, default_value_t = TransportKind::Udp)]
+    transport: TransportKind,
+
+    /// Send a custom payload instead of the standard magic packet, given as hex
+    /// with `{mac}` standing in for the target MAC address
+    #[arg(long, value_parser = validate_pattern)]
+    pattern: Option<String>,
+
+    /// Append a SecureOn password read from this file (falls back to the
+    /// WOL_PASSWORD environment variable)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pattern", "password_prompt"])]
+    password_file: Option<PathBuf>,
+
+    /// A TCP port that accepts (or refuses) connections while the host is
+    /// awake, for --until-up and keepalive
+    #[arg(long, default_value_t = 22)]
+    probe_port: u16,
+
+    /// Pad the payload with zero bytes up to this size, for NICs that ignore
+    /// short frames
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u16).range(1..=1472))]
+    pad_to: Option<u16>,
+
+    /// Prompt for the SecureOn password without echoing it
+    #[arg(long, conflicts_with = "pattern")]
+    password_prompt: bool,
+
+    /// Have this MikroTik router send the packet with `/tool wol`, via the
+    /// plain-text RouterOS API at host[:port] (port 8728; the API over TLS
+    /// isn't supported, so the login crosses the network unencrypted)
+    #[arg(
+        long,
+        value_name = "ROUTER",
+        conflicts_with_all = ["transport", "pattern", "pcap_out", "password_file", "password_prompt", "pad_to"]
+    )]
+    routeros: Option<String>,
+
+    /// The RouterOS API user
+    #[arg(
+        long,
+        value_name = "USER",
+        default_value = "admin",
+        requires = "routeros"
+    )]
+    routeros_user: String,
+
+    /// The RouterOS API password
+    #[arg(
+        long,
+        value_name = "PASSWORD",
+        env = "WOL_ROUTEROS_PASSWORD",
+        hide_env_values = true,
+        default_value = ""
+    )]
+    routeros_password: String,
+
+    /// The router interface to send the packet out of, instead of the
+    /// router's default
+    #[arg(long, value_name = "INTERFACE", requires = "routeros")]
+    routeros_interface: Option<String>,
+
+    /// Append every packet sent over UDP to this pcap file, for auditing
+    #[arg(long, value_name = "FILE")]
+    pcap_out: Option<PathBuf>,
+
+    /// Explain on stderr how the packet is routed and what is sent
+    #[arg(long)]
+    explain: bool,
+
+    /// Knock on these ports of the destination before each send, as a
+    /// comma-separated list of PORT[/tcp|/udp], TCP by default
+    #[arg(long, value_name = "PORTS", value_delimiter = ',', value_parser = knock::parse_knock, conflicts_with = "routeros")]
+    knock: Vec<knock::Knock>,
+
+    /// Milliseconds to wait after each knock
+    #[arg(long, value_name = "MS", default_value_t = 500, requires = "knock")]
+    knock_delay: u64,
+
+    /// Send to public (non-private) destinations without asking first
+    #[arg(short, long)]
+    yes: bool,
 }
 
-fn validate_mac(mac: &str) -> Result<String, String> {
-    let parts: Vec<&str> = mac.split(':').collect();
-    if parts.len() != 6 {
-        return Err(String::from("Invalid MAC address format"));
+/// How names are resolved, for the wake destination and the hosts probed,
+/// and for the switches `locate` queries.
+#[derive(clap::Args, Debug)]
+struct ResolveArgs {
+    /// Use an IPv4 address first when the address resolves to both families
+    #[arg(long, conflicts_with = "prefer_ipv6")]
+    prefer_ipv4: bool,
+
+    /// Use an IPv6 address first when the address resolves to both families
+    #[arg(long)]
+    prefer_ipv6: bool,
+
+    /// Seconds to wait for the system resolver (or --dns-server)
+    #[arg(long, value_name = "SECS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    resolve_timeout: u64,
+
+    /// Resolve names with this DNS server instead of the system resolver, as
+    /// an IP address with an optional port; queries go over plain UDP, as
+    /// DNS over TLS isn't supported
+    #[arg(long, value_name = "SERVER", value_parser = parse_dns_server)]
+    dns_server: Option<SocketAddr>,
+}
+
+impl ResolveArgs {
+    fn options(&self) -> resolve::Options {
+        let prefer = match (self.prefer_ipv4, self.prefer_ipv6) {
+            (true, _) => Some(resolve::Family::V4),
+            (_, true) => Some(resolve::Family::V6),
+            _ => None,
+        };
+        resolve::Options {
+            prefer,
+            timeout: Duration::from_secs(self.resolve_timeout),
+            server: self.dns_server,
+        }
     }
-    for part in parts {
-        if part.len() != 2 || !part.chars().all(|c| c.is_ascii_hexdigit()) {
-            return Err(String::from("Invalid MAC address format"));
+}
+
+/// How the waits between retries grow: between --until-up resends, between
+/// the probes of --until-up, `rdp` and `vnc` while a host comes up, and
+/// between attempts at the RouterOS and SNMP calls. `bench` polls at a
+/// fixed rate instead, so as not to skew what it measures.
+#[derive(clap::Args, Debug)]
+struct BackoffArgs {
+    /// How the wait between retries grows
+    #[arg(long, value_enum, default_value_t = backoff::Strategy::Exponential)]
+    backoff: backoff::Strategy,
+
+    /// Seconds to wait before the first retry
+    #[arg(long, value_name = "SECS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    backoff_initial: u64,
+
+    /// The longest wait between retries, in seconds
+    #[arg(long, value_name = "SECS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    backoff_max: u64,
+}
+
+impl BackoffArgs {
+    fn policy(&self) -> backoff::Backoff {
+        backoff::Backoff {
+            strategy: self.backoff,
+            initial: Duration::from_secs(self.backoff_initial),
+            max: Duration::from_secs(self.backoff_max),
         }
     }
-    Ok(mac.to_owned())
 }
 
-fn build_magic_packet(mac: &str) -> Vec<u8> {
+/// When `wake` sends and whether it keeps resending. Only `wake` takes these;
+/// the other commands that wake a host have their own timing.
+#[derive(clap::Args, Debug)]
+struct ScheduleArgs {
+    /// Wait this long before waking, e.g. 45m or 1h30m
+    #[arg(long = "in", value_name = "DELAY", value_parser = schedule::parse_delay, conflicts_with = "at")]
+    delay: Option<Duration>,
+
+    /// Wait until this local time before waking, as YYYY-MM-DD HH:MM (a Z or
+    /// +HH:MM suffix gives the offset instead); the local offset is the one
+    /// in effect at that time, read with the `date` command, so without it
+    /// (e.g. on Windows) give an offset
+    #[arg(long, value_name = "TIME", value_parser = schedule::parse_time)]
+    at: Option<schedule::Time>,
+
+    /// Keep waking until this host answers on --probe-port, backing off
+    /// between attempts, and fail if it isn't up by --deadline
+    #[arg(long, value_name = "HOST")]
+    until_up: Option<String>,
+
+    /// Seconds --until-up keeps trying
+    #[arg(long, value_name = "SECS", default_value_t = 120, requires = "until_up", value_parser = clap::value_parser!(u64).range(1..))]
+    deadline: u64,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a wake-up packet (the default without a subcommand)
+    Wake {
+        #[command(flatten)]
+        wake: WakeArgs,
+        #[command(flatten)]
+        schedule: ScheduleArgs,
+    },
+    /// Decode a captured magic packet and explain any malformation
+    Decode {
+        /// The payload as a hex string, or a file containing the raw bytes
+        input: String,
+    },
+    /// Find the switch port a MAC was last seen on, via SNMP (BRIDGE-MIB)
+    Locate {
+        /// The MAC address to look for
+        #[arg(value_parser = validate_mac)]
+        mac: MacAddr,
+
+        /// A managed switch to query, as host or host:port; may be repeated
+        #[arg(short, long = "switch", value_name = "SWITCH", required = true)]
+        switches: Vec<String>,
+
+        /// The SNMPv2c community
+        #[arg(short, long, default_value = "public")]
+        community: String,
+
+        #[command(flatten)]
+        resolve: ResolveArgs,
+
+        #[command(flatten)]
+        backoff: BackoffArgs,
+    },
+    /// Print host entries found in another tool's data
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+    /// Generate files for packaging
+    Generate {
+        #[command(subcommand)]
+        target: GenerateTarget,
+    },
+    /// Learn the MAC address of a host from its IP address or name
+    Detect {
+        /// The host, as an IP address or name
+        host: String,
+    },
+    /// Find the IP address a MAC currently holds
+    Whois {
+        /// The MAC address to look for
+        #[arg(value_parser = validate_mac)]
+        mac: MacAddr,
+    },
+    /// Watch a host and wake it again whenever it stops answering
+    Keepalive {
+        /// The host to watch, as a name or IP address
+        host: String,
+        /// Seconds between checks
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+        /// Stop after this long, e.g. 8h or 1h30m, instead of running until
+        /// interrupted
+        #[arg(long = "for", value_name = "DURATION", value_parser = schedule::parse_delay)]
+        run_for: Option<Duration>,
+        #[command(flatten)]
+        wake: WakeArgs,
+    },
+    /// Measure how long a host takes to wake, over several sleep cycles
+    Bench {
+        /// The host to wake and probe, as a name or IP address
+        host: String,
+        /// How many wake-ups to time
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        runs: u32,
+        /// Seconds to wait for the host to answer after each wake-up
+        #[arg(long, value_name = "SECS", default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..))]
+        run_timeout: u64,
+        #[command(flatten)]
+        wake: WakeArgs,
+    },
+    /// Wake a host, wait for its RDP port and open a remote-desktop client
+    Rdp(RemoteArgs),
+    /// Wake a host, wait for its VNC port and open a VNC viewer
+    Vnc(RemoteArgs),
+    /// Check that magic packets can be built and sent from this machine
+    Selftest,
+    /// Look for likely reasons a wake isn't arriving, most likely first
+    Doctor {
+        /// A host to check as well, as a name or IP address
+        host: Option<String>,
+        /// A TCP port that accepts (or refuses) connections while the host is
+        /// awake
+        #[arg(long, default_value_t = 22)]
+        probe_port: u16,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// The shell to complete for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct RemoteArgs {
+    /// The host to connect to, as a name or IP address
+    host: String,
+    /// The port the remote-desktop service listens on [default: 3389 for
+    /// RDP, 5900 for VNC]
+    #[arg(long, value_name = "PORT")]
+    remote_port: Option<u16>,
+    /// Seconds to wait for the service to accept connections
+    #[arg(long, value_name = "SECS", default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..))]
+    wait: u64,
+    #[command(flatten)]
+    wake: WakeArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum GenerateTarget {
+    /// Man pages for the program and each of its subcommands
+    Man {
+        /// Write a page per command into this directory instead of printing
+        /// the main page
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportSource {
+    /// Magic packets and ARP traffic in a pcap capture
+    Pcap {
+        /// The capture file (classic libpcap format)
+        file: PathBuf,
+    },
+    /// Hosts with a `mac` hostvar in an Ansible INI or YAML inventory
+    Ansible {
+        /// The inventory file
+        file: PathBuf,
+    },
+    /// Hosts with a MAC address in an nmap XML report (`nmap -oX`)
+    Nmap {
+        /// The XML report file
+        file: PathBuf,
+    },
+    /// IP/MAC pairs in `ip neigh`, `arp -a` or `/proc/net/arp` output, such
+    /// as pasted from a machine without this tool
+    Neigh {
+        /// The listing file, or `-` for standard input [default: -]
+        file: Option<PathBuf>,
+    },
+    /// Every MAC address in free text, such as a copied router page or
+    /// `ipconfig /all` output
+    Text {
+        /// The text file, or `-` for standard input [default: -]
+        file: Option<PathBuf>,
+    },
+    /// `Host` blocks with a `#wol-mac:` comment in an OpenSSH client config
+    Ssh {
+        /// The config file [default: ~/.ssh/config]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum TransportKind {
+    /// Broadcast the packet as a UDP datagram
+    Udp,
+    /// Deliver the packet over a TCP connection to the address
+    Tcp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Decides whether a stream should be colored, following https://no-color.org
+    /// in `auto` mode: any non-empty NO_COLOR value disables color.
+    fn enabled(self, is_terminal: bool, no_color: Option<&str>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && no_color.is_none_or(str::is_empty),
+        }
+    }
+}
+
+/// The stagger between sends with --all-addresses, in the spirit of Happy
+/// Eyeballs (RFC 8305), so one family's failure doesn't delay the other.
+const FAN_OUT_DELAY: Duration = Duration::from_millis(25);
+
+/// How long a wake-up with --watch-unreachable watches for a router's ICMP
+/// unreachable after each send.
+const UNREACHABLE_WINDOW: Duration = Duration::from_millis(200);
+
+/// How many times a RouterOS or SNMP call is tried before giving up.
+const BACKEND_ATTEMPTS: u32 = 3;
+
+/// The exit status when --timeout expires, the same as timeout(1) uses.
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+const GREEN: &str = "32";
+const BOLD_RED: &str = "1;31";
+
+fn paint(text: &str, style: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{style}m{text}\x1b[0m")
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Parses a MAC argument: six two-digit hex octets separated by colons.
+fn validate_mac(mac: &str) -> Result<MacAddr, String> {
+    mac.parse()
+        .ok()
+        .filter(|_| !mac.contains('-'))
+        .ok_or_else(|| String::from("Invalid MAC address format"))
+}
+
+fn parse_dns_server(server: &str) -> Result<SocketAddr, String> {
+    server
+        .parse()
+        .or_else(|_| server.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| {
+            format!(
+                "invalid DNS server '{server}' (expected an IP address, optionally with a port)"
+            )
+        })
+}
+
+fn validate_pattern(pattern: &str) -> Result<String, String> {
+    build_pattern_packet(pattern, MacAddr::new([0; 6]))?;
+    Ok(pattern.to_owned())
+}
+
+/// Builds the payload of a --pattern, failing on anything but whole hex
+/// bytes and `{mac}` placeholders, or on a pattern with neither.
+fn build_pattern_packet(pattern: &str, mac: MacAddr) -> Result<Vec<u8>, String> {
+    let invalid =
+        || String::from("Invalid pattern: expected hex bytes with optional {mac} placeholders");
     let mut packet = Vec::new();
-    // First add 6 bytes of 0xFF
-    packet.extend_from_slice(&[0xFF; 6]);
-    // Second add the MAC address repeated 16 times
-    let mac_bytes: Vec<u8> = mac
-        .split(':')
-        .map(|part| u8::from_str_radix(part, 16).unwrap())
+    for (i, segment) in pattern.split("{mac}").enumerate() {
+        if i > 0 {
+            packet.extend_from_slice(&mac.octets());
+        }
+        let hex: Vec<char> = segment.chars().filter(|c| !c.is_whitespace()).collect();
+        for pair in hex.chunks(2) {
+            let [high, low] = *pair else {
+                return Err(invalid());
+            };
+            let digit = |c: char| c.to_digit(16).ok_or_else(invalid);
+            packet.push((digit(high)? * 16 + digit(low)?) as u8);
+        }
+    }
+    if packet.is_empty() {
+        return Err(String::from(
+            "Invalid pattern: it is empty, so there would be nothing to send",
+        ));
+    }
+    Ok(packet)
+}
+
+fn build_magic_packet(mac: MacAddr) -> [u8; 102] {
+    wake_on_lan::magic_packet(mac)
+}
+
+/// Socket settings for UDP sends beyond the destination.
+#[derive(Debug, Clone, Default)]
+struct UdpOptions {
+    /// The IPv4 TTL for multicast destinations.
+    multicast_ttl: Option<u32>,
+    /// A local IPv4 address whose interface multicast should leave through.
+    multicast_interface: Option<Ipv4Addr>,
+    /// How long to watch for an ICMP unreachable error after a unicast send.
+    unreachable_window: Option<Duration>,
+    /// The broadcast addresses of directly attached networks, which no
+    /// router stands in front of, so they aren't watched.
+    attached_broadcasts: Vec<Ipv4Addr>,
+    /// A local address to send from instead of letting the kernel pick.
+    source: Option<IpAddr>,
+}
+
+/// Sends one datagram and returns the local address it left from.
+fn send_udp(
+    packet: &[u8],
+    destination: SocketAddr,
+    options: &UdpOptions,
+) -> Result<SocketAddr, String> {
+    let multicast = destination.ip().is_multicast();
+    let bind: SocketAddr = match (destination, options.source, options.multicast_interface) {
+        (_, Some(source), _) if source.is_ipv4() != destination.is_ipv4() => {
+            return Err(format!(
+                "--source {source} and destination {destination} are different address families"
+            ));
+        }
+        (_, Some(source), _) => (source, 0).into(),
+        // Linux and the BSDs send multicast out of the interface that owns
+        // the bound source address.
+        (SocketAddr::V4(_), None, Some(interface)) if multicast => (interface, 0).into(),
+        (SocketAddr::V4(_), ..) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        (SocketAddr::V6(_), ..) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket =
+        UdpSocket::bind(bind).map_err(|e| format!("failed to bind socket to {bind}: {e}"))?;
+    socket
+        .set_broadcast(true)
+        .map_err(|e| format!("failed to enable broadcast: {e}"))?;
+    if let (true, true, Some(ttl)) = (multicast, destination.is_ipv4(), options.multicast_ttl) {
+        socket
+            .set_multicast_ttl_v4(ttl)
+            .map_err(|e| format!("failed to set multicast TTL: {e}"))?;
+    }
+    let broadcast = matches!(
+        destination.ip(),
+        IpAddr::V4(ip) if ip.is_broadcast() || options.attached_broadcasts.contains(&ip)
+    );
+    let watch = options
+        .unreachable_window
+        .filter(|_| !multicast && !broadcast);
+    // Only a connected socket is told about ICMP errors for its datagrams.
+    if watch.is_some() {
+        socket
+            .connect(destination)
+            .and_then(|()| socket.send(packet))
+    } else {
+        socket.send_to(packet, destination)
+    }
+    .map_err(|e| format!("failed to send packet to {destination}: {e}"))?;
+    if let Some(window) = watch {
+        socket
+            .set_read_timeout(Some(window))
+            .map_err(|e| format!("failed to set read timeout: {e}"))?;
+        // A port unreachable (ConnectionRefused) comes from the target's own
+        // stack, so the packet got there; host and network unreachable come
+        // from a router that dropped it.
+        if let Err(e) = socket.recv(&mut [0; 64])
+            && matches!(
+                e.kind(),
+                io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable
+            )
+        {
+            return Err(format!(
+                "a router rejected the packet to {destination} ({e})"
+            ));
+        }
+    }
+    socket
+        .local_addr()
+        .map_err(|e| format!("failed to read local address: {e}"))
+}
+
+/// The system's UDP sockets, set up per send as [`send_udp`] does for the
+/// wake options.
+struct SystemTransport {
+    options: UdpOptions,
+}
+
+impl SystemTransport {
+    fn new(args: &WakeArgs) -> Self {
+        let attached_broadcasts = if args.watch_unreachable {
+            route::table()
+                .unwrap_or_default()
+                .iter()
+                .filter(|route| route.gateway.is_none())
+                .map(route::Route::broadcast)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        SystemTransport {
+            options: UdpOptions {
+                multicast_ttl: Some(args.multicast_ttl),
+                multicast_interface: args.multicast_interface,
+                unreachable_window: args.watch_unreachable.then_some(UNREACHABLE_WINDOW),
+                source: args.source,
+                attached_broadcasts,
+            },
+        }
+    }
+}
+
+impl Transport for SystemTransport {
+    fn send_udp(&mut self, destination: SocketAddr, payload: &[u8]) -> io::Result<SocketAddr> {
+        send_udp(payload, destination, &self.options).map_err(io::Error::other)
+    }
+}
+
+fn send_tcp(packet: &[u8], destinations: &[SocketAddr]) -> Result<(), String> {
+    let mut stream = TcpStream::connect(destinations)
+        .map_err(|e| format!("failed to connect to {}: {e}", destinations[0]))?;
+    let peer = stream
+        .peer_addr()
+        .map_err(|e| format!("failed to read peer address: {e}"))?;
+    stream
+        .write_all(packet)
+        .map_err(|e| format!("failed to send packet to {peer}: {e}"))?;
+    Ok(())
+}
+
+fn run_decode(input: &str, color: bool) -> Result<bool, String> {
+    let payload = if Path::new(input).is_file() {
+        fs::read(input).map_err(|e| format!("failed to read {input}: {e}"))?
+    } else {
+        decode::parse_hex(input).map_err(|e| format!("invalid payload: {e}"))?
+    };
+    let decoded = decode::decode(&payload);
+
+    println!("Length:   {} bytes", payload.len());
+    if let Some(mac) = decoded.mac {
+        println!("MAC:      {}", MacAddr::from(mac));
+    }
+    if let Some(password) = &decoded.secure_on {
+        println!("SecureOn: {}", decode::format_secure_on(password));
+    }
+    if decoded.problems.is_empty() {
+        println!("{}", paint("Valid magic packet", GREEN, color));
+    } else {
+        for problem in &decoded.problems {
+            println!("{} {problem}", paint("Problem:", BOLD_RED, color));
+        }
+    }
+    Ok(decoded.problems.is_empty())
+}
+
+/// The MAC address selftest puts in its packets; locally administered, so it
+/// can't match a real NIC.
+const SELFTEST_MAC: MacAddr = MacAddr::new([0x02, 0x00, 0x5e, 0x10, 0x00, 0x01]);
+
+enum Check {
+    Pass,
+    Skip(String),
+    Fail(String),
+}
+
+/// Sends a magic packet to a listener on `loopback` and checks it arrives
+/// unchanged. `Skip` means the address family isn't available here.
+fn check_loopback(loopback: IpAddr) -> Check {
+    let Ok(listener) = UdpSocket::bind((loopback, 0)) else {
+        return Check::Skip(format!("{loopback} is not configured"));
+    };
+    let result = listener
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .and_then(|()| listener.local_addr())
+        .map_err(|e| format!("failed to set up the listener: {e}"))
+        .and_then(|addr| {
+            let packet = build_magic_packet(SELFTEST_MAC);
+            send_udp(&packet, addr, &UdpOptions::default())?;
+            let mut buf = [0; 1500];
+            let (len, _) = listener
+                .recv_from(&mut buf)
+                .map_err(|e| format!("nothing arrived: {e}"))?;
+            if buf[..len] == packet {
+                Ok(())
+            } else {
+                Err(format!(
+                    "received {len} bytes that differ from the packet sent"
+                ))
+            }
+        });
+    match result {
+        Ok(()) => Check::Pass,
+        Err(e) => Check::Fail(e),
+    }
+}
+
+/// Checks that the system lets unprivileged sockets broadcast, by sending an
+/// empty datagram to the discard port of the limited broadcast address.
+fn check_broadcast() -> Check {
+    let destination = SocketAddr::from((Ipv4Addr::BROADCAST, 9));
+    match send_udp(&[], destination, &UdpOptions::default()) {
+        Ok(_) => Check::Pass,
+        Err(e) => Check::Fail(e),
+    }
+}
+
+fn run_selftest(color: bool) -> Result<bool, String> {
+    let decoded = decode::decode(&build_magic_packet(SELFTEST_MAC));
+    let encoding = if decoded.problems.is_empty() {
+        Check::Pass
+    } else {
+        Check::Fail(decoded.problems.join("; "))
+    };
+    let checks = [
+        ("magic packet encodes and decodes", encoding),
+        (
+            "UDP round trip over 127.0.0.1",
+            check_loopback(Ipv4Addr::LOCALHOST.into()),
+        ),
+        (
+            "UDP round trip over ::1",
+            check_loopback(Ipv6Addr::LOCALHOST.into()),
+        ),
+        ("broadcast to 255.255.255.255", check_broadcast()),
+    ];
+    let mut passed = true;
+    for (name, check) in checks {
+        match check {
+            Check::Pass => println!("{}  {name}", paint("ok  ", GREEN, color)),
+            Check::Skip(reason) => println!("skip  {name}: {reason}"),
+            Check::Fail(reason) => {
+                passed = false;
+                println!("{}  {name}: {reason}", paint("FAIL", BOLD_RED, color));
+            }
+        }
+    }
+    Ok(passed)
+}
+
+fn run_doctor(host: Option<&str>, probe_port: u16, color: bool) -> Result<bool, String> {
+    let routes = route::table();
+    let mut findings = doctor::check_networks(routes.as_deref(), doctor::send_empty);
+    if let Some(host) = host {
+        findings.extend(doctor::check_host(host, probe_port, routes.as_deref().ok()));
+    }
+    findings.sort_by_key(|finding| finding.severity);
+    for finding in &findings {
+        let label = match finding.severity {
+            doctor::Severity::Problem => paint("Problem:", BOLD_RED, color),
+            doctor::Severity::Warning => paint("Warning:", BOLD_RED, color),
+            doctor::Severity::Info => String::from("Info:   "),
+        };
+        println!("{label} {}", finding.message);
+    }
+    let problems = findings
+        .iter()
+        .filter(|finding| finding.severity == doctor::Severity::Problem)
+        .count();
+    if problems == 0 {
+        println!("{}", paint("No problems found", GREEN, color));
+    }
+    Ok(problems == 0)
+}
+
+fn run_locate(
+    mac: MacAddr,
+    switches: &[String],
+    community: &str,
+    options: &resolve::Options,
+    backoff: backoff::Backoff,
+) -> Result<bool, String> {
+    let mac_bytes = mac.octets();
+    let mut found = false;
+    for switch in switches {
+        let result = resolve::resolve_endpoint(switch, 161, options).and_then(|agents| {
+            retry(switch, backoff, || {
+                snmp::locate(agents[0], community, mac_bytes, Duration::from_secs(2))
+            })
+        });
+        match result {
+            Ok(Some(location)) => {
+                found = true;
+                let mut line = format!("{switch}: bridge port {}", location.bridge_port);
+                if let Some(if_index) = location.if_index {
+                    line.push_str(&format!(", ifIndex {if_index}"));
+                }
+                if let Some(name) = location.if_name {
+                    line.push_str(&format!(" ({name})"));
+                }
+                println!("{line}");
+            }
+            Ok(None) => println!("{switch}: not in forwarding table"),
+            Err(e) => println!("{switch}: {e}"),
+        }
+    }
+    Ok(found)
+}
+
+/// Reads a text file, or standard input when `file` is missing or `-`.
+fn read_input(file: Option<&Path>) -> Result<String, String> {
+    match file {
+        Some(file) if file != Path::new("-") => {
+            fs::read_to_string(file).map_err(|e| format!("failed to read {}: {e}", file.display()))
+        }
+        _ => io::read_to_string(io::stdin())
+            .map_err(|e| format!("failed to read standard input: {e}")),
+    }
+}
+
+fn run_import(source: &ImportSource) -> Result<bool, String> {
+    let entries = match source {
+        ImportSource::Pcap { file } => {
+            let data =
+                fs::read(file).map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+            import::pcap::parse(&data)?
+        }
+        ImportSource::Ansible { file } => {
+            let inventory = fs::read_to_string(file)
+                .map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+            import::ansible::parse(&inventory)?
+        }
+        ImportSource::Nmap { file } => {
+            let xml = fs::read_to_string(file)
+                .map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+            import::nmap::parse(&xml)?
+        }
+        ImportSource::Neigh { file } => import::neigh::parse(&read_input(file.as_deref())?),
+        ImportSource::Text { file } => import::text::parse(&read_input(file.as_deref())?),
+        ImportSource::Ssh { file } => {
+            let file = match file {
+                Some(file) => file.clone(),
+                None => env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".ssh").join("config"))
+                    .ok_or("HOME is not set; give the config file explicitly")?,
+            };
+            let config = fs::read_to_string(&file)
+                .map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+            import::ssh::parse(&config)?
+        }
+    };
+    print_entries(&entries);
+    Ok(true)
+}
+
+fn run_detect(host: &str) -> Result<bool, String> {
+    let mut found = false;
+    for addr in resolve::resolve(host, 9)? {
+        let ip = addr.ip();
+        match neighbor::resolve(ip, Duration::from_secs(2)) {
+            Ok(Some(neighbor)) => {
+                found = true;
+                println!(
+                    "{ip} is at {} on {}",
+                    MacAddr::from(neighbor.mac),
+                    neighbor.interface
+                );
+            }
+            Ok(None) => println!("{ip}: no answer; it may be asleep or not on a local network"),
+            Err(e) => println!("{ip}: failed to read the neighbor table: {e}"),
+        }
+    }
+    Ok(found)
+}
+
+fn run_whois(mac: MacAddr) -> Result<bool, String> {
+    let mac_bytes = mac.octets();
+    let routes = route::table().unwrap_or_default();
+    let neighbors = neighbor::find_mac(
+        mac_bytes,
+        &neighbor::sweepable(&routes),
+        Duration::from_secs(3),
+    )
+    .map_err(|e| format!("failed to read the neighbor table: {e}"))?;
+    if neighbors.is_empty() {
+        println!("{mac}: not seen on any directly attached network");
+    }
+    for neighbor in &neighbors {
+        println!("{mac} is at {} on {}", neighbor.ip, neighbor.interface);
+    }
+    Ok(!neighbors.is_empty())
+}
+
+fn run_bench(
+    host: &str,
+    runs: u32,
+    run_timeout: u64,
+    wake_args: &WakeArgs,
+    color_stdout: bool,
+) -> Result<bool, String> {
+    let addrs = resolve_probe(host, wake_args.probe_port, wake_args)?;
+    let mut wake = prepare_wake(wake_args, env::var("WOL_PASSWORD").ok())?;
+    let mut transport = SystemTransport::new(wake_args);
+    let mut latencies = Vec::new();
+    for run in 1..=runs {
+        if probe::host(&addrs, Duration::from_secs(1)) {
+            println!("Run {run}/{runs}: waiting for {host} to go to sleep");
+            while probe::host(&addrs, Duration::from_secs(1)) {
+                thread::sleep(Duration::from_secs(2));
+            }
+        }
+        send_wake(wake_args, &mut wake, &mut transport, color_stdout)?;
+        let start = Instant::now();
+        let deadline = start + Duration::from_secs(run_timeout);
+        loop {
+            if probe::host(&addrs, Duration::from_secs(1)) {
+                let latency = start.elapsed();
+                println!("Run {run}/{runs}: up after {:.1}s", latency.as_secs_f64());
+                latencies.push(latency);
+                break;
+            }
+            if Instant::now() >= deadline {
+                println!("Run {run}/{runs}: did not wake within {run_timeout}s");
+                break;
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+    let Some(stats) = latency_stats(&mut latencies) else {
+        return Err(format!("{host} never woke up"));
+    };
+    println!(
+        "{} of {runs} woke: min {:.1}s, median {:.1}s, p95 {:.1}s",
+        latencies.len(),
+        stats.min.as_secs_f64(),
+        stats.median.as_secs_f64(),
+        stats.p95.as_secs_f64()
+    );
+    Ok(latencies.len() == runs as usize)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct LatencyStats {
+    min: Duration,
+    median: Duration,
+    p95: Duration,
+}
+
+/// Summarizes wake latencies, using the nearest-rank method for percentiles.
+fn latency_stats(latencies: &mut [Duration]) -> Option<LatencyStats> {
+    latencies.sort();
+    let rank = |percentile: usize| {
+        let index = (percentile * latencies.len()).div_ceil(100);
+        latencies[index.max(1) - 1]
+    };
+    Some(LatencyStats {
+        min: *latencies.first()?,
+        median: rank(50),
+        p95: rank(95),
+    })
+}
+
+/// Wakes the host unless the service already answers, waits for the service
+/// to accept connections and hands the host to the platform's client.
+fn run_remote(
+    protocol: remote::Protocol,
+    args: &RemoteArgs,
+    color_stdout: bool,
+) -> Result<bool, String> {
+    let host = &args.host;
+    let port = args.remote_port.unwrap_or(protocol.default_port());
+    let timeout = Duration::from_secs(1);
+    let addrs = resolve_probe(host, port, &args.wake)?;
+    if !probe::accepting(&addrs, timeout) {
+        let mut wake = prepare_wake(&args.wake, env::var("WOL_PASSWORD").ok())?;
+        let mut transport = SystemTransport::new(&args.wake);
+        send_wake(&args.wake, &mut wake, &mut transport, color_stdout)?;
+        println!("Waiting for {} on {host} port {port}", protocol.name());
+        let deadline = Instant::now() + Duration::from_secs(args.wait);
+        let backoff = args.wake.backoff.policy();
+        let mut attempt = 0;
+        while !probe::accepting(&addrs, timeout) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(format!(
+                    "{host} did not accept {} connections within {}s",
+                    protocol.name(),
+                    args.wait
+                ));
+            }
+            thread::sleep(backoff.delay(attempt).min(remaining));
+            attempt = attempt.saturating_add(1);
+        }
+    }
+    let client = remote::launch(protocol, host, port)?;
+    println!("Opened {host} in {client}");
+    Ok(true)
+}
+
+/// Watches the host and wakes it whenever it is down, for `run_for` or until
+/// interrupted. Succeeds if the host was up at the last check.
+fn run_keepalive(
+    host: &str,
+    interval: Duration,
+    run_for: Option<Duration>,
+    wake_args: &WakeArgs,
+    color_stdout: bool,
+    color_stderr: bool,
+) -> Result<bool, String> {
+    let addrs = resolve_probe(host, wake_args.probe_port, wake_args)?;
+    let mut wake = prepare_wake(wake_args, env::var("WOL_PASSWORD").ok())?;
+    let mut transport = SystemTransport::new(wake_args);
+    let mut was_up = None;
+    let is_up = || {
+        let up = probe::host(&addrs, Duration::from_secs(2));
+        if was_up != Some(up) {
+            println!("{host} is {}", if up { "up" } else { "down" });
+            was_up = Some(up);
+        }
+        up
+    };
+    let wake = || {
+        if let Err(e) = send_wake(wake_args, &mut wake, &mut transport, color_stdout) {
+            eprintln!("{} {e}", paint("Error:", BOLD_RED, color_stderr));
+        }
+    };
+    let stop = run_for.map(|run_for| Instant::now() + run_for);
+    Ok(keep_alive(interval, stop, is_up, wake))
+}
+
+/// Checks `is_up` every `interval` and calls `wake` whenever it says the
+/// host is down, until a check at or after `stop`, or forever without one.
+/// Returns what the last check said.
+fn keep_alive(
+    interval: Duration,
+    stop: Option<Instant>,
+    mut is_up: impl FnMut() -> bool,
+    mut wake: impl FnMut(),
+) -> bool {
+    loop {
+        let up = is_up();
+        if !up {
+            wake();
+        }
+        let mut wait = interval;
+        if let Some(stop) = stop {
+            let remaining = stop.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return up;
+            }
+            wait = wait.min(remaining);
+        }
+        thread::sleep(wait);
+    }
+}
+
+/// Wakes the host after any --in or --at delay, having resolved and
+/// confirmed the destination beforehand, and with --until-up keeps
+/// sending, waiting as --backoff says in between, until the host answers a
+/// probe or the deadline passes.
+fn run_wake(args: &WakeArgs, schedule: &ScheduleArgs, color_stdout: bool) -> Result<bool, String> {
+    let mut wake = prepare_wake(args, env::var("WOL_PASSWORD").ok())?;
+    let mut transport = SystemTransport::new(args);
+    let until_up = match &schedule.until_up {
+        Some(host) => Some((host, resolve_probe(host, args.probe_port, args)?)),
+        None => None,
+    };
+    let delay = match schedule.at {
+        Some(at) => {
+            let mut offset_unknown = false;
+            let delay = at
+                .until(SystemTime::now(), |epoch| {
+                    schedule::local_offset_at(epoch).unwrap_or_else(|| {
+                        offset_unknown = true;
+                        0
+                    })
+                })
+                .map_err(|e| format!("--at: {e}"))?;
+            if offset_unknown {
+                return Err(String::from(
+                    "--at: the local UTC offset is read with the `date` command, which isn't available or failed; give the time an offset such as +02:00 or Z",
+                ));
+            }
+            Some(delay)
+        }
+        None => schedule.delay,
+    };
+    if let Some(delay) = delay {
+        println!("Waking in {}", schedule::format_delay(delay));
+        thread::sleep(delay);
+    }
+    let Some((host, addrs)) = until_up else {
+        return send_wake(args, &mut wake, &mut transport, color_stdout).map(|()| true);
+    };
+    let backoff = args.backoff.policy();
+    let deadline = Duration::from_secs(schedule.deadline);
+    let is_up = || probe::host(&addrs, Duration::from_secs(1));
+    match wake_until_up(
+        args,
+        &mut wake,
+        &mut transport,
+        backoff,
+        deadline,
+        is_up,
+        color_stdout,
+    )? {
+        Some(elapsed) => {
+            println!(
+                "{} after {}s",
+                paint(&format!("{host} is up"), GREEN, color_stdout),
+                elapsed.as_secs()
+            );
+            Ok(true)
+        }
+        None => Err(format!(
+            "{host} did not come up within {}s",
+            schedule.deadline
+        )),
+    }
+}
+
+/// Sends `wake` until `is_up` says the host answers, probing at least once
+/// after each send and waiting as `backoff` says before the next, both
+/// between sends and between the probes after one. Returns how long the
+/// host took, or `None` once `deadline` has passed.
+fn wake_until_up(
+    args: &WakeArgs,
+    wake: &mut Wake,
+    transport: &mut dyn Transport,
+    backoff: backoff::Backoff,
+    deadline: Duration,
+    mut is_up: impl FnMut() -> bool,
+    color_stdout: bool,
+) -> Result<Option<Duration>, String> {
+    let start = Instant::now();
+    let deadline = start + deadline;
+    let mut attempt = 0;
+    loop {
+        let delay = backoff.delay(attempt);
+        attempt = attempt.saturating_add(1);
+        send_wake(args, wake, transport, color_stdout)?;
+        let next_send = (Instant::now() + delay).min(deadline);
+        let mut probe = 0;
+        loop {
+            if is_up() {
+                return Ok(Some(start.elapsed()));
+            }
+            let remaining = next_send.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            thread::sleep(backoff.delay(probe).min(remaining));
+            probe = probe.saturating_add(1);
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+    }
+}
+
+/// Calls `call` for `what` until it succeeds, at most `BACKEND_ATTEMPTS`
+/// times, waiting as `backoff` says between failures. Returns the last
+/// error.
+fn retry<T>(
+    what: &str,
+    backoff: backoff::Backoff,
+    mut call: impl FnMut() -> Result<T, String>,
+) -> Result<T, String> {
+    let mut attempt = 0;
+    loop {
+        match call() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt + 1 >= BACKEND_ATTEMPTS => return Err(e),
+            Err(e) => {
+                let delay = backoff.delay(attempt);
+                eprintln!(
+                    "Warning: {what}: {e}; retrying in {}",
+                    schedule::format_delay(delay)
+                );
+                thread::sleep(delay);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+fn run_generate(target: &GenerateTarget) -> Result<bool, String> {
+    let GenerateTarget::Man { out_dir } = target;
+    let command = Args::command();
+    match out_dir {
+        Some(dir) => clap_mangen::generate_to(command, dir)
+            .map_err(|e| format!("failed to write man pages to {}: {e}", dir.display()))?,
+        None => clap_mangen::Man::new(command)
+            .render(&mut io::stdout())
+            .map_err(|e| format!("failed to write man page: {e}"))?,
+    }
+    Ok(true)
+}
+
+fn run_completions(shell: Shell, out: &mut dyn Write) -> Result<bool, String> {
+    let mut command = Args::command();
+    // Render into memory first: the generators panic if the writer fails,
+    // e.g. when piped into `head`.
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, env!("CARGO_BIN_NAME"), &mut script);
+    out.write_all(&script)
+        .map_err(|e| format!("failed to write completion script: {e}"))?;
+    Ok(true)
+}
+
+fn print_entries(entries: &[import::Entry]) {
+    if entries.is_empty() {
+        println!("No hosts found");
+        return;
+    }
+    let rows: Vec<[String; 3]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                MacAddr::from(entry.mac).to_string(),
+                entry
+                    .ip
+                    .map_or_else(|| String::from("-"), |ip| ip.to_string()),
+                entry.name.clone().unwrap_or_else(|| String::from("-")),
+            ]
+        })
         .collect();
-    for _ in 0..16 {
-        packet.extend_from_slice(&mac_bytes);
+    let ip_width = rows.iter().map(|row| row[1].len()).max().unwrap().max(2);
+    println!("{:<17}  {:<ip_width$}  NAME", "MAC", "IP");
+    for [mac, ip, name] in &rows {
+        println!("{mac:<17}  {ip:<ip_width$}  {name}");
+    }
+}
+
+/// Finds the SecureOn password, if any, along with where it came from: from
+/// --password-file, an interactive prompt, or else the WOL_PASSWORD
+/// environment variable. None of these show up in shell history or the
+/// process list.
+fn secure_on_password(
+    args: &WakeArgs,
+    env: Option<String>,
+) -> Result<Option<(String, Vec<u8>)>, String> {
+    let (source, text) = if let Some(path) = &args.password_file {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        (path.display().to_string(), text)
+    } else if args.password_prompt {
+        (
+            String::from("the prompt"),
+            prompt_hidden("SecureOn password: ")?,
+        )
+    } else if let Some(text) = env {
+        (String::from("WOL_PASSWORD"), text)
+    } else {
+        return Ok(None);
+    };
+    match wake_on_lan::parse_secure_on(&text) {
+        Some(password) => Ok(Some((source, password))),
+        None => Err(format!(
+            "invalid SecureOn password from {source}: expected 6 hex octets (like a MAC) or 4 dotted decimal bytes"
+        )),
+    }
+}
+
+/// Reads a line from the terminal with echo turned off (via `stty` on Unix).
+fn prompt_hidden(prompt: &str) -> Result<String, String> {
+    let stty = |setting: &str| {
+        process::Command::new("stty")
+            .arg(setting)
+            .stdin(process::Stdio::inherit())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    eprint!("{prompt}");
+    let hidden = cfg!(unix) && stty("-echo");
+    let mut line = String::new();
+    let result = io::stdin().read_line(&mut line);
+    if hidden {
+        stty("echo");
+        eprintln!();
+    }
+    result.map_err(|e| format!("failed to read password: {e}"))?;
+    Ok(line)
+}
+
+/// Replaces each IPv4 host address on a directly attached network with the
+/// broadcast address of the network the kernel would send it out on.
+fn to_subnet_broadcast(destinations: &[SocketAddr], routes: &[route::Route]) -> Vec<SocketAddr> {
+    destinations
+        .iter()
+        .map(|destination| {
+            let IpAddr::V4(ip) = destination.ip() else {
+                return *destination;
+            };
+            match route::lookup(routes, ip) {
+                Some(r)
+                    if r.gateway.is_none()
+                        && !r.destination.is_loopback()
+                        && r.prefix_len() < 31 =>
+                {
+                    SocketAddr::new(r.broadcast().into(), destination.port())
+                }
+                _ => *destination,
+            }
+        })
+        .collect()
+}
+
+/// Whether packets to `ip` stay on private or local networks: RFC 1918 and
+/// shared (CGNAT) space, unique local IPv6, and loopback, link-local and
+/// multicast addresses.
+fn is_private_scope(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let shared = v4.octets()[0] == 100 && v4.octets()[1] & 0xc0 == 64;
+            v4.is_private()
+                || shared
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_multicast()
+                || v4.is_unspecified()
+        }
+        IpAddr::V6(v6) => {
+            v6.is_unique_local()
+                || v6.is_loopback()
+                || v6.is_unicast_link_local()
+                || v6.is_multicast()
+                || v6.is_unspecified()
+        }
+    }
+}
+
+/// Describes the first destination that would put packets on the internet:
+/// a public address, or the limited broadcast when `source_of` says it leaves
+/// from a public address.
+fn public_destination(
+    destinations: &[SocketAddr],
+    source_of: impl Fn(SocketAddr) -> Option<IpAddr>,
+) -> Option<String> {
+    destinations
+        .iter()
+        .find_map(|destination| match destination.ip() {
+            IpAddr::V4(ip) if ip.is_broadcast() => source_of(*destination)
+                .filter(|source| !is_private_scope(*source))
+                .map(|source| format!("{ip} leaves from the public address {source}")),
+            ip if !is_private_scope(ip) => Some(format!("{ip} is a public address")),
+            _ => None,
+        })
+}
+
+/// Asks on the terminal before sending to a public destination, and refuses
+/// when there's no terminal to ask on.
+fn confirm_public(description: &str) -> Result<(), String> {
+    if !io::stdin().is_terminal() {
+        return Err(format!(
+            "{description}; refusing to send onto the internet without --yes"
+        ));
+    }
+    eprint!("{description}. Send the packet anyway? [y/N] ");
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| format!("failed to read the answer: {e}"))?;
+    if matches!(answer.trim(), "y" | "Y" | "yes") {
+        Ok(())
+    } else {
+        Err(String::from("not sent"))
+    }
+}
+
+/// Describes how a UDP or TCP wake-up will leave this machine, for `--explain`.
+fn explain(
+    args: &WakeArgs,
+    destinations: &[SocketAddr],
+    packet: &[u8],
+    routes: Option<&[route::Route]>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let destination = destinations[0];
+    let how = if args.address.parse::<IpAddr>().is_ok() {
+        String::from("an IP literal")
+    } else {
+        let all: Vec<String> = destinations.iter().map(|d| d.ip().to_string()).collect();
+        format!("resolved from {} to {}", args.address, all.join(", "))
+    };
+    lines.push(format!("Destination: {destination} ({how})"));
+
+    let route = match (destination.ip(), routes) {
+        (IpAddr::V4(ip), Some(routes)) => route::lookup(routes, ip),
+        _ => None,
+    };
+    let kind = match (destination.ip(), route) {
+        (IpAddr::V4(Ipv4Addr::BROADCAST), _) => String::from(
+            "limited broadcast; it only leaves through the interface the route lookup \
+             picks, so use a directed broadcast address to reach another network",
+        ),
+        (IpAddr::V4(ip), Some(r))
+            if r.gateway.is_none()
+                && r.prefix_len() < 31
+                && u32::from(ip) | u32::from(r.mask) == u32::MAX =>
+        {
+            format!(
+                "directed broadcast for {}/{}",
+                r.destination,
+                r.prefix_len()
+            )
+        }
+        (ip, _) if ip.is_multicast() => String::from("multicast"),
+        _ => String::from("unicast; the last hop must still know the target's MAC address"),
+    };
+    lines.push(format!("Kind:        {kind}"));
+
+    lines.push(match (route, routes, destination) {
+        (Some(r), _, _) => {
+            let via = match r.gateway {
+                Some(gateway) => format!("via gateway {gateway}"),
+                None => String::from("on-link"),
+            };
+            format!(
+                "Route:       {} {via}, matching {}/{} (metric {})",
+                r.interface,
+                r.destination,
+                r.prefix_len(),
+                r.metric
+            )
+        }
+        (None, _, SocketAddr::V6(_)) => {
+            String::from("Route:       chosen by the kernel (IPv6 routes are not inspected)")
+        }
+        (None, Some(_), _) => String::from("Route:       no matching route"),
+        (None, None, _) => String::from("Route:       routing table unavailable"),
+    });
+    lines.push(match (args.source, route::source_address(destination)) {
+        (Some(source), _) => format!("Source:      {source}, given with --source"),
+        (None, Ok(source)) => format!("Source:      {source}, picked by the kernel"),
+        (None, Err(e)) => format!("Source:      unknown ({e})"),
+    });
+    lines.push(match (args.transport, args.source) {
+        (TransportKind::Udp, Some(source)) => {
+            format!("Socket:      UDP, bound to an ephemeral port on {source}, SO_BROADCAST set")
+        }
+        (TransportKind::Udp, None) => String::from(
+            "Socket:      UDP, bound to an ephemeral port on all addresses, SO_BROADCAST set",
+        ),
+        (TransportKind::Tcp, _) => format!(
+            "Socket:      TCP, trying {} address(es) in turn",
+            destinations.len()
+        ),
+    });
+    lines.push(format!("Payload:     {} bytes", packet.len()));
+    for (row, chunk) in packet.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        lines.push(format!("  {:04x}  {}", row * 16, hex.join(" ")));
+    }
+    lines
+}
+
+/// Sends the packet as `kind` says: UDP goes to the first destination
+/// through `transport`, TCP tries each in turn until one connects.
+fn deliver(
+    kind: TransportKind,
+    transport: &mut dyn Transport,
+    packet: &[u8],
+    destinations: &[SocketAddr],
+    pcap: Option<&mut capture::Writer>,
+) -> Result<(), String> {
+    match kind {
+        TransportKind::Udp => {
+            let destination = destinations[0];
+            let source = transport
+                .send_udp(destination, packet)
+                .map_err(|e| e.to_string())?;
+            if let Some(pcap) = pcap {
+                pcap.write_udp(SystemTime::now(), source, destination, packet)
+                    .map_err(|e| format!("failed to write pcap record: {e}"))?;
+            }
+        }
+        TransportKind::Tcp => send_tcp(packet, destinations)?,
+    }
+    Ok(())
+}
+
+/// A wake-up resolved, built and confirmed once, so that resending it
+/// neither repeats the lookups nor asks again.
+struct Wake {
+    mac: MacAddr,
+    packet: Vec<u8>,
+    /// The addresses to send to: one, or all of them with --all-addresses
+    /// or TCP. With --routeros, the router's API addresses.
+    destinations: Vec<SocketAddr>,
+    pcap: Option<capture::Writer>,
+}
+
+/// Resolves a host to probe on `port`, once, with the wake's resolver
+/// options, so probes neither look it up again nor wait on a resolver.
+fn resolve_probe(host: &str, port: u16, args: &WakeArgs) -> Result<Vec<SocketAddr>, String> {
+    resolve::resolve_with(host, port, &args.resolve.options()).map_err(|e| {
+        format!("{e}; a sleeping host often doesn't answer name queries, so give its IP address")
+    })
+}
+
+/// Works out everything a wake-up needs before the first send: the MAC, the
+/// payload and the destinations, asking before any public one. `env_password`
+/// is the value of WOL_PASSWORD, if set.
+fn prepare_wake(args: &WakeArgs, env_password: Option<String>) -> Result<Wake, String> {
+    let mac = match args.mac {
+        Some(mac) => mac,
+        None => {
+            let text = clipboard::read()?;
+            clipboard::find_mac(&text)
+                .ok_or_else(|| String::from("no MAC address found on the clipboard"))?
+                .into()
+        }
+    };
+    // --password-file and --password-prompt conflict with these modes on
+    // the command line; a password from the environment is only ignored.
+    let env_password = match env_password {
+        Some(_) if args.pattern.is_some() || args.routeros.is_some() => {
+            eprintln!(
+                "Warning: WOL_PASSWORD is ignored, as --pattern and --routeros don't send a SecureOn password"
+            );
+            None
+        }
+        env_password => env_password,
+    };
+    let password = secure_on_password(args, env_password)?;
+    let mut packet = match (&args.pattern, password) {
+        (Some(pattern), _) => build_pattern_packet(pattern, mac)?,
+        (None, None) => build_magic_packet(mac).to_vec(),
+        (None, Some((_, password))) => Payload::new(mac, Some(&password))
+            .map_err(|e| e.to_string())?
+            .to_vec(),
+    };
+    if let Some(size) = args.pad_to {
+        let size = usize::from(size).max(packet.len());
+        packet.resize(size, 0);
+    }
+    if let Some(router) = &args.routeros {
+        let destinations =
+            resolve::resolve_endpoint(router, routeros::DEFAULT_PORT, &args.resolve.options())?;
+        if args.explain {
+            let interface = args
+                .routeros_interface
+                .as_deref()
+                .unwrap_or("its default interface");
+            eprintln!(
+                "RouterOS:    {router} at {} sends the packet out of {interface}; local routes are not involved",
+                destinations[0]
+            );
+        }
+        return Ok(Wake {
+            mac,
+            packet,
+            destinations,
+            pcap: None,
+        });
+    }
+
+    if args.transport == TransportKind::Tcp && args.source.is_some() {
+        return Err(String::from(
+            "--source only applies to UDP; the TCP transport can't choose a source address",
+        ));
+    }
+    if args.transport == TransportKind::Tcp && args.subnet_broadcast {
+        return Err(String::from(
+            "--subnet-broadcast only applies to UDP; TCP can't reach a broadcast address",
+        ));
+    }
+    let mut destinations =
+        resolve::resolve_with(&args.address, args.port, &args.resolve.options())?;
+    if args.subnet_broadcast {
+        let routes =
+            route::table().map_err(|e| format!("failed to read the routing table: {e}"))?;
+        let rewritten = to_subnet_broadcast(&destinations, &routes);
+        if args.explain {
+            for (from, to) in destinations.iter().zip(&rewritten).filter(|(a, b)| a != b) {
+                eprintln!(
+                    "Rewrite:     {} to {}, its network's broadcast",
+                    from.ip(),
+                    to.ip()
+                );
+            }
+        }
+        destinations = rewritten;
+    }
+    if args.explain {
+        let routes = route::table().ok();
+        for line in explain(args, &destinations, &packet, routes.as_deref()) {
+            eprintln!("{line}");
+        }
+    }
+
+    if !(args.all_addresses || args.transport == TransportKind::Tcp) {
+        destinations.truncate(1);
+    }
+    if !args.knock.is_empty() {
+        knock::check_target(destinations[0].ip(), &route::table().unwrap_or_default())?;
+    }
+    if !args.yes
+        && let Some(description) = public_destination(&destinations, |destination| {
+            route::source_address(destination).ok()
+        })
+    {
+        confirm_public(&description)?;
+    }
+
+    let pcap = match &args.pcap_out {
+        Some(_) if args.transport != TransportKind::Udp => {
+            return Err(String::from(
+                "--pcap-out is only supported with the UDP transport",
+            ));
+        }
+        Some(path) => Some(
+            capture::Writer::open(path)
+                .map_err(|e| format!("failed to open {}: {e}", path.display()))?,
+        ),
+        None => None,
+    };
+    Ok(Wake {
+        mac,
+        packet,
+        destinations,
+        pcap,
+    })
+}
+
+/// Sends a prepared wake-up once: knocking first if asked, then over UDP
+/// through `transport`, TCP or the RouterOS API.
+fn send_wake(
+    args: &WakeArgs,
+    wake: &mut Wake,
+    transport: &mut dyn Transport,
+    color_stdout: bool,
+) -> Result<(), String> {
+    let mac = wake.mac;
+    if let Some(router) = &args.routeros {
+        retry(router, args.backoff.policy(), || {
+            let mut client = routeros::Client::connect(&wake.destinations, Duration::from_secs(5))?;
+            client.login(&args.routeros_user, &args.routeros_password)?;
+            client.wake(&mac.to_string(), args.routeros_interface.as_deref())
+        })?;
+        println!(
+            "{} {mac} via {router}",
+            paint("Wake up packet sent to", GREEN, color_stdout)
+        );
+        return Ok(());
+    }
+
+    if !args.knock.is_empty() {
+        knock::send(
+            wake.destinations[0].ip(),
+            &args.knock,
+            Duration::from_millis(args.knock_delay),
+        )?;
+    }
+
+    if args.all_addresses {
+        let mut delivered = 0;
+        for (i, destination) in resolve::interleave(&wake.destinations)
+            .into_iter()
+            .enumerate()
+        {
+            if i > 0 {
+                thread::sleep(FAN_OUT_DELAY);
+            }
+            match deliver(
+                args.transport,
+                transport,
+                &wake.packet,
+                &[destination],
+                wake.pcap.as_mut(),
+            ) {
+                Ok(()) => {
+                    delivered += 1;
+                    println!("{destination}: sent");
+                }
+                Err(e) => println!("{destination}: {e}"),
+            }
+        }
+        if delivered == 0 {
+            return Err(format!(
+                "failed to send to any of the {} resolved addresses",
+                wake.destinations.len()
+            ));
+        }
+    } else {
+        deliver(
+            args.transport,
+            transport,
+            &wake.packet,
+            &wake.destinations,
+            wake.pcap.as_mut(),
+        )?;
+    }
+
+    println!(
+        "{} {mac}",
+        paint("Wake up packet sent to", GREEN, color_stdout)
+    );
+    Ok(())
+}
+
+/// Parses the command line like [`Parser::try_parse_from`], except that
+/// `--version` wins over missing required arguments, as clap's own version
+/// flag does, so `wol decode -V` prints the version.
+fn parse_args<T: Into<OsString> + Clone>(
+    argv: impl IntoIterator<Item = T>,
+) -> Result<Args, clap::Error> {
+    let argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+    let error = match Args::try_parse_from(&argv) {
+        Ok(args) => return Ok(args),
+        Err(e) if e.kind() == clap::error::ErrorKind::MissingRequiredArgument => e,
+        Err(e) => return Err(e),
+    };
+    let matches = Args::command()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)?;
+    let mut levels = vec![&matches];
+    levels.extend(matches.subcommand().map(|(_, sub)| sub));
+    if !levels.iter().any(|m| m.get_flag("version")) {
+        return Err(error);
+    }
+    let mut version_argv = vec![argv[0].clone(), OsString::from("--version")];
+    if let Some(format) = levels
+        .iter()
+        .find_map(|m| m.get_one::<OutputFormat>("output"))
+        .and_then(|format| format.to_possible_value())
+    {
+        version_argv.extend([OsString::from("--output"), format.get_name().into()]);
+    }
+    Args::try_parse_from(version_argv)
+}
+
+fn main() {
+    let args = parse_args(env::args_os()).unwrap_or_else(|e| e.exit());
+    let no_color = env::var("NO_COLOR").ok();
+    let color_stdout = args
+        .color
+        .enabled(io::stdout().is_terminal(), no_color.as_deref());
+    let color_stderr = args
+        .color
+        .enabled(io::stderr().is_terminal(), no_color.as_deref());
+
+    if args.version {
+        match args.output {
+            Some(OutputFormat::Json) => println!("{}", build_info::json()),
+            _ => print!("{}", Args::command().render_version()),
+        }
+        return;
+    }
+
+    if let Some(secs) = args.timeout {
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(secs));
+            eprintln!(
+                "{} timed out after {secs}s",
+                paint("Error:", BOLD_RED, color_stderr)
+            );
+            process::exit(TIMEOUT_EXIT_CODE);
+        });
+    }
+
+    let result = match &args.command {
+        Some(Command::Decode { input }) => run_decode(input, color_stdout),
+        Some(Command::Locate {
+            mac,
+            switches,
+            community,
+            resolve,
+            backoff,
+        }) => run_locate(
+            *mac,
+            switches,
+            community,
+            &resolve.options(),
+            backoff.policy(),
+        ),
+        Some(Command::Import { source }) => run_import(source),
+        Some(Command::Generate { target }) => run_generate(target),
+        Some(Command::Detect { host }) => run_detect(host),
+        Some(Command::Whois { mac }) => run_whois(*mac),
+        Some(Command::Keepalive {
+            host,
+            interval,
+            run_for,
+            wake,
+        }) => run_keepalive(
+            host,
+            Duration::from_secs(*interval),
+            *run_for,
+            wake,
+            color_stdout,
+            color_stderr,
+        ),
+        Some(Command::Bench {
+            host,
+            runs,
+            run_timeout,
+            wake,
+        }) => run_bench(host, *runs, *run_timeout, wake, color_stdout),
+        Some(Command::Rdp(remote_args)) => {
+            run_remote(remote::Protocol::Rdp, remote_args, color_stdout)
+        }
+        Some(Command::Vnc(remote_args)) => {
+            run_remote(remote::Protocol::Vnc, remote_args, color_stdout)
+        }
+        Some(Command::Doctor { host, probe_port }) => {
+            run_doctor(host.as_deref(), *probe_port, color_stdout)
+        }
+        Some(Command::Selftest) => run_selftest(color_stdout),
+        Some(Command::Completions { shell }) => run_completions(*shell, &mut io::stdout()),
+        Some(Command::Wake { wake, schedule }) => run_wake(wake, schedule, color_stdout),
+        None => run_wake(&args.wake, &args.schedule, color_stdout),
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("{} {e}", paint("Error:", BOLD_RED, color_stderr));
+            process::exit(1);
+        }
     }
-    packet
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wake_on_lan::transport::{MockTransport, Packet};
+
+    const MAC: MacAddr = MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
 
     #[test]
     fn test_validate_mac_valid() {
@@ -91,50 +1948,730 @@ mod tests {
 
     #[test]
     fn test_build_magic_packet_length() {
-        let packet = build_magic_packet("00:11:22:33:44:55");
+        let packet = build_magic_packet(MAC);
         // 6 bytes of 0xFF + 16 * 6 bytes of MAC = 102 bytes
         assert_eq!(packet.len(), 102);
     }
 
     #[test]
     fn test_build_magic_packet_header() {
-        let packet = build_magic_packet("00:11:22:33:44:55");
+        let packet = build_magic_packet(MAC);
         assert_eq!(&packet[0..6], &[0xFF; 6]);
     }
 
     #[test]
     fn test_build_magic_packet_mac_repetitions() {
-        let packet = build_magic_packet("b8:ae:ed:9c:c7:89");
+        let packet = build_magic_packet("b8:ae:ed:9c:c7:89".parse().unwrap());
         let expected_mac = [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89];
         for i in 0..16 {
             let offset = 6 + i * 6;
             assert_eq!(&packet[offset..offset + 6], &expected_mac);
         }
     }
-}
 
-fn main() {
-    let args = Args::parse();
-    let magic_packet = build_magic_packet(&args.mac);
-    let dest = format!("{}:{}", args.address, args.port);
+    #[test]
+    fn test_validate_pattern_valid() {
+        assert!(validate_pattern("ffffffffffff{mac}").is_ok());
+        assert!(validate_pattern("aa bb {mac} cc").is_ok());
+        assert!(validate_pattern("0102").is_ok());
+    }
 
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Error: failed to bind socket: {e}");
-            process::exit(1);
+    #[test]
+    fn test_validate_pattern_invalid() {
+        assert!(validate_pattern("fff{mac}").is_err());
+        assert!(validate_pattern("zz{mac}").is_err());
+        assert!(validate_pattern("{ma}").is_err());
+        assert!(validate_pattern("+f").is_err());
+        assert!(validate_pattern("").is_err());
+        assert!(validate_pattern("  ").is_err());
+    }
+
+    #[test]
+    fn test_build_pattern_packet_substitutes_mac() {
+        let packet = build_pattern_packet("aa bb {mac} cc", MAC).unwrap();
+        assert_eq!(
+            packet,
+            [0xaa, 0xbb, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0xcc]
+        );
+    }
+
+    #[test]
+    fn test_build_pattern_packet_matches_magic_packet() {
+        let pattern = format!("ffffffffffff{}", "{mac}".repeat(16));
+        let mac = "b8:ae:ed:9c:c7:89".parse().unwrap();
+        assert_eq!(
+            build_pattern_packet(&pattern, mac).unwrap(),
+            build_magic_packet(mac)
+        );
+    }
+
+    #[test]
+    fn test_send_tcp_delivers_packet() {
+        use std::io::Read;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dest = listener.local_addr().unwrap();
+        let packet = build_magic_packet(MAC);
+
+        send_tcp(&packet, &[dest]).unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        assert_eq!(received, packet);
+    }
+
+    #[test]
+    fn test_color_choice_auto_requires_terminal() {
+        assert!(ColorChoice::Auto.enabled(true, None));
+        assert!(!ColorChoice::Auto.enabled(false, None));
+    }
+
+    #[test]
+    fn test_color_choice_auto_respects_no_color() {
+        assert!(!ColorChoice::Auto.enabled(true, Some("1")));
+        assert!(ColorChoice::Auto.enabled(true, Some("")));
+    }
+
+    #[test]
+    fn test_color_choice_explicit() {
+        assert!(ColorChoice::Always.enabled(false, Some("1")));
+        assert!(!ColorChoice::Never.enabled(true, None));
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("ok", GREEN, true), "\x1b[32mok\x1b[0m");
+        assert_eq!(paint("ok", GREEN, false), "ok");
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_cli_flat_invocation_is_wake() {
+        let args = Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "-p", "7"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.wake.mac, Some(MAC));
+        assert_eq!(args.wake.port, 7);
+    }
+
+    #[test]
+    fn test_cli_wake_subcommand() {
+        let args =
+            Args::try_parse_from(["wol", "wake", "--color", "never", "-m", "00:11:22:33:44:55"])
+                .unwrap();
+        let Some(Command::Wake { wake, .. }) = args.command else {
+            panic!("expected the wake subcommand");
+        };
+        assert_eq!(wake.mac, Some(MAC));
+        assert_eq!(args.color, ColorChoice::Never);
+        assert!(Args::try_parse_from(["wol", "wake"]).is_err());
+        assert!(Args::try_parse_from(["wol", "wake", "--clipboard"]).is_ok());
+        assert!(Args::try_parse_from(["wol", "--clipboard", "-m", "00:11:22:33:44:55"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_version() {
+        let args = Args::try_parse_from(["wol", "--version", "--output", "json"]).unwrap();
+        assert!(args.version);
+        assert_eq!(args.output, Some(OutputFormat::Json));
+        assert!(Args::try_parse_from(["wol", "-V"]).unwrap().version);
+        assert!(Args::try_parse_from(["wol", "--output", "json"]).is_err());
+
+        let args = parse_args(["wol", "decode", "-V", "--output", "json"]).unwrap();
+        assert!(args.version);
+        assert_eq!(args.output, Some(OutputFormat::Json));
+        assert!(parse_args(["wol", "whois", "--version"]).unwrap().version);
+        assert!(parse_args(["wol", "decode"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_delay() {
+        let args =
+            Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "--in", "1h30m"]).unwrap();
+        assert_eq!(args.schedule.delay, Some(Duration::from_secs(5400)));
+        assert!(
+            Args::try_parse_from([
+                "wol",
+                "-m",
+                "00:11:22:33:44:55",
+                "--in",
+                "5m",
+                "--at",
+                "2030-01-01 07:00",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_schedule_is_wake_only() {
+        let mac = "00:11:22:33:44:55";
+        for command in ["bench", "keepalive", "rdp", "vnc"] {
+            let argv = ["wol", command, "nas", "-m", mac];
+            assert!(Args::try_parse_from(argv).is_ok(), "{command}");
+            for schedule in [["--in", "5m"], ["--until-up", "nas"]] {
+                let argv = [&argv[..], &schedule].concat();
+                assert!(
+                    Args::try_parse_from(argv).is_err(),
+                    "{command} {schedule:?}"
+                );
+            }
         }
-    };
+        let args = Args::try_parse_from(["wol", "wake", "-m", mac, "--until-up", "nas"]).unwrap();
+        let Some(Command::Wake { schedule, .. }) = args.command else {
+            panic!("expected the wake subcommand");
+        };
+        assert_eq!(schedule.until_up.as_deref(), Some("nas"));
+    }
+
+    #[test]
+    fn test_check_loopback() {
+        assert!(matches!(
+            check_loopback(Ipv4Addr::LOCALHOST.into()),
+            Check::Pass
+        ));
+    }
+
+    #[test]
+    fn test_cli_locate_resolver_options() {
+        let args = Args::try_parse_from([
+            "wol",
+            "locate",
+            "00:11:22:33:44:55",
+            "-s",
+            "switch.corp.example",
+            "--dns-server",
+            "10.8.0.1",
+        ])
+        .unwrap();
+        let Some(Command::Locate { resolve, .. }) = args.command else {
+            panic!("expected the locate subcommand");
+        };
+        assert_eq!(
+            resolve.options().server,
+            Some("10.8.0.1:53".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_dns_server() {
+        assert_eq!(
+            parse_dns_server("10.8.0.1"),
+            Ok("10.8.0.1:53".parse().unwrap())
+        );
+        assert_eq!(
+            parse_dns_server("[fd00::53]:5353"),
+            Ok("[fd00::53]:5353".parse().unwrap())
+        );
+        assert!(parse_dns_server("dns.corp.example").is_err());
+    }
+
+    #[test]
+    fn test_cli_backoff() {
+        let mac = ["wol", "-m", "00:11:22:33:44:55"];
+        let args = Args::try_parse_from([&mac[..], &["--backoff", "fixed"]].concat()).unwrap();
+        assert_eq!(args.wake.backoff.backoff, backoff::Strategy::Fixed);
+        assert_eq!(args.wake.backoff.backoff_initial, 5);
+        let args = Args::try_parse_from([
+            "wol",
+            "locate",
+            "00:11:22:33:44:55",
+            "-s",
+            "core-sw",
+            "--backoff-max",
+            "60",
+        ])
+        .unwrap();
+        let Some(Command::Locate { backoff, .. }) = args.command else {
+            panic!("expected the locate subcommand");
+        };
+        assert_eq!(backoff.policy().max, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_retry() {
+        let backoff = backoff::Backoff {
+            strategy: backoff::Strategy::Fixed,
+            initial: Duration::ZERO,
+            max: Duration::ZERO,
+        };
+        let mut calls = 0;
+        let result = retry("router", backoff, || {
+            calls += 1;
+            if calls < 2 {
+                Err(String::from("refused"))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(2));
+
+        let mut calls = 0;
+        let result: Result<(), String> = retry("router", backoff, || {
+            calls += 1;
+            Err(format!("failure {calls}"))
+        });
+        assert_eq!(result, Err(String::from("failure 3")));
+        assert_eq!(calls, BACKEND_ATTEMPTS);
+    }
+
+    #[test]
+    fn test_cli_timeout() {
+        let args =
+            Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "--timeout", "10"]).unwrap();
+        assert_eq!(args.timeout, Some(10));
+        let args = Args::try_parse_from(["wol", "doctor", "--timeout", "3"]).unwrap();
+        assert_eq!(args.timeout, Some(3));
+        assert!(
+            Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "--timeout", "0"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_to_subnet_broadcast() {
+        use route::tests::network;
+        let routes = [
+            network([0, 0, 0, 0], [0, 0, 0, 0], Some([10, 0, 0, 1])),
+            network([10, 0, 0, 0], [255, 255, 255, 0], None),
+            network([192, 168, 6, 0], [255, 255, 254, 0], None),
+        ];
+        let destinations: Vec<SocketAddr> =
+            ["192.168.6.40:9", "10.0.0.7:7", "8.8.8.8:9", "[fe80::1]:9"]
+                .iter()
+                .map(|addr| addr.parse().unwrap())
+                .collect();
+        let expected: Vec<SocketAddr> = [
+            "192.168.7.255:9",
+            "10.0.0.255:7",
+            "8.8.8.8:9",
+            "[fe80::1]:9",
+        ]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+        assert_eq!(to_subnet_broadcast(&destinations, &routes), expected);
+    }
+
+    #[test]
+    fn test_public_destination() {
+        let addrs = |list: &[&str]| -> Vec<SocketAddr> {
+            list.iter().map(|addr| addr.parse().unwrap()).collect()
+        };
+        let private = addrs(&[
+            "192.168.1.255:9",
+            "10.0.0.5:9",
+            "100.64.1.1:9",
+            "224.0.0.1:9",
+            "[fd00::1]:9",
+            "[fe80::1]:9",
+        ]);
+        assert_eq!(public_destination(&private, |_| None), None);
+        assert_eq!(
+            public_destination(&addrs(&["10.0.0.5:9", "203.0.113.255:9"]), |_| None),
+            Some(String::from("203.0.113.255 is a public address"))
+        );
+        assert_eq!(
+            public_destination(&addrs(&["[2001:db8::1]:9"]), |_| None),
+            Some(String::from("2001:db8::1 is a public address"))
+        );
+
+        let broadcast = addrs(&["255.255.255.255:9"]);
+        assert_eq!(
+            public_destination(&broadcast, |_| Some("192.168.1.5".parse().unwrap())),
+            None
+        );
+        assert_eq!(
+            public_destination(&broadcast, |_| Some("198.51.100.7".parse().unwrap())),
+            Some(String::from(
+                "255.255.255.255 leaves from the public address 198.51.100.7"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cli_knock() {
+        let args = Args::try_parse_from([
+            "wol",
+            "-m",
+            "00:11:22:33:44:55",
+            "--knock",
+            "7000,8000/udp",
+            "--knock-delay",
+            "100",
+        ])
+        .unwrap();
+        assert_eq!(args.wake.knock.len(), 2);
+        assert_eq!(args.wake.knock[1].protocol, knock::Protocol::Udp);
+        assert!(
+            Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "--knock-delay", "100"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_pad_to_range() {
+        let parse =
+            |size| Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "--pad-to", size]);
+        assert_eq!(parse("1472").unwrap().wake.pad_to, Some(1472));
+        assert!(parse("1473").is_err());
+        assert!(parse("0").is_err());
+    }
+
+    #[test]
+    fn test_cli_subcommand_rejects_flat_arguments() {
+        assert!(Args::try_parse_from(["wol", "decode", "--color", "always", "ff"]).is_ok());
+        assert!(Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "decode", "ff"]).is_err());
+        assert!(Args::try_parse_from(["wol"]).is_err());
+    }
 
-    if let Err(e) = socket.set_broadcast(true) {
-        eprintln!("Error: failed to enable broadcast: {e}");
-        process::exit(1);
+    #[test]
+    fn test_generate_man_pages() {
+        let dir = env::temp_dir().join(format!("wol-man-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        run_generate(&GenerateTarget::Man {
+            out_dir: Some(dir.clone()),
+        })
+        .unwrap();
+        let main_page = fs::read_to_string(dir.join("wake-on-lan.1")).unwrap();
+        let import_page = fs::read_to_string(dir.join("wake-on-lan-import-nmap.1")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(main_page.contains("decode"));
+        assert!(main_page.contains("routeros"));
+        assert!(import_page.contains("nmap"));
     }
 
-    if let Err(e) = socket.send_to(&magic_packet, &dest) {
-        eprintln!("Error: failed to send packet to {dest}: {e}");
-        process::exit(1);
+    #[test]
+    fn test_completions() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut script = Vec::new();
+            run_completions(shell, &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("wake-on-lan"), "{shell}");
+            assert!(script.contains("routeros-interface"), "{shell}");
+        }
     }
 
-    println!("Wake up packet sent to {}", args.mac);
+    #[test]
+    fn test_explain() {
+        let args =
+            Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "-a", "127.255.255.255"])
+                .unwrap()
+                .wake;
+        let routes = [route::Route {
+            interface: String::from("lo"),
+            destination: Ipv4Addr::new(127, 0, 0, 0),
+            mask: Ipv4Addr::new(255, 0, 0, 0),
+            gateway: None,
+            metric: 0,
+        }];
+        let packet = build_magic_packet(MAC);
+        let lines = explain(
+            &args,
+            &["127.255.255.255:9".parse().unwrap()],
+            &packet,
+            Some(&routes),
+        );
+        assert_eq!(lines[0], "Destination: 127.255.255.255:9 (an IP literal)");
+        assert_eq!(lines[1], "Kind:        directed broadcast for 127.0.0.0/8");
+        assert_eq!(
+            lines[2],
+            "Route:       lo on-link, matching 127.0.0.0/8 (metric 0)"
+        );
+        assert!(lines[4].contains("SO_BROADCAST"));
+        assert_eq!(lines[5], "Payload:     102 bytes");
+        assert_eq!(
+            lines[6],
+            "  0000  ff ff ff ff ff ff 00 11 22 33 44 55 00 11 22 33"
+        );
+        assert_eq!(lines.len(), 6 + 7);
+
+        let lines = explain(
+            &args,
+            &["255.255.255.255:9".parse().unwrap()],
+            &packet,
+            None,
+        );
+        assert!(lines[1].starts_with("Kind:        limited broadcast"));
+        assert_eq!(lines[2], "Route:       routing table unavailable");
+    }
+
+    #[test]
+    fn test_secure_on_password_sources() {
+        let wake_args = |extra: &[&str]| {
+            let mut argv = vec!["wol", "-m", "00:11:22:33:44:55"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().wake
+        };
+        assert_eq!(secure_on_password(&wake_args(&[]), None).unwrap(), None);
+        assert_eq!(
+            secure_on_password(&wake_args(&[]), Some(String::from("1.2.3.4"))).unwrap(),
+            Some((String::from("WOL_PASSWORD"), vec![1, 2, 3, 4]))
+        );
+        assert!(secure_on_password(&wake_args(&[]), Some(String::from("hunter2"))).is_err());
+
+        let path = env::temp_dir().join(format!("wol-password-{}", process::id()));
+        fs::write(&path, "0a:0b:0c:0d:0e:0f\n").unwrap();
+        let args = wake_args(&["--password-file", path.to_str().unwrap()]);
+        let password = secure_on_password(&args, Some(String::from("1.2.3.4")));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            password.unwrap(),
+            Some((path.display().to_string(), vec![10, 11, 12, 13, 14, 15]))
+        );
+    }
+
+    #[test]
+    fn test_env_password_ignored_with_pattern() {
+        let wake_args = |extra: &[&str]| {
+            let mut argv = vec!["wol", "-m", "00:11:22:33:44:55", "-a", "127.0.0.1"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().wake
+        };
+        let args = wake_args(&["--pattern", "ff{mac}"]);
+        let wake = prepare_wake(&args, Some(String::from("1.2.3.4"))).unwrap();
+        assert_eq!(wake.packet, build_pattern_packet("ff{mac}", MAC).unwrap());
+
+        let args = wake_args(&[]);
+        let wake = prepare_wake(&args, Some(String::from("1.2.3.4"))).unwrap();
+        assert_eq!(wake.packet[102..], [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_keep_alive_stops_at_its_window() {
+        let stop = Instant::now() + Duration::from_millis(50);
+        let mut checks = 0u32;
+        let mut wakes = 0;
+        let is_up = || {
+            checks += 1;
+            checks.is_multiple_of(2)
+        };
+        keep_alive(Duration::from_millis(10), Some(stop), is_up, || wakes += 1);
+        assert!(Instant::now() >= stop);
+        assert!(checks >= 2);
+        assert_eq!(wakes, checks.div_ceil(2));
+    }
+
+    #[test]
+    fn test_cli_keepalive_for() {
+        let args = Args::try_parse_from([
+            "wol",
+            "keepalive",
+            "nas",
+            "-m",
+            "00:11:22:33:44:55",
+            "--for",
+            "8h",
+        ])
+        .unwrap();
+        let Some(Command::Keepalive { run_for, .. }) = args.command else {
+            panic!("expected the keepalive subcommand");
+        };
+        assert_eq!(run_for, Some(Duration::from_secs(8 * 3600)));
+    }
+
+    fn wake_until_up_with(
+        transport: &mut MockTransport,
+        deadline: Duration,
+        is_up: impl FnMut() -> bool,
+    ) -> Result<Option<Duration>, String> {
+        let args = Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "-a", "127.0.0.1"])
+            .unwrap()
+            .wake;
+        let mut wake = prepare_wake(&args, None).unwrap();
+        // No wait between sends, so each send gets exactly one probe.
+        let backoff = backoff::Backoff {
+            strategy: backoff::Strategy::Fixed,
+            initial: Duration::ZERO,
+            max: Duration::ZERO,
+        };
+        wake_until_up(&args, &mut wake, transport, backoff, deadline, is_up, false)
+    }
+
+    #[test]
+    fn test_wake_until_up_resends_until_the_host_answers() {
+        let mut transport = MockTransport::new();
+        let mut probes = 0;
+        let is_up = || {
+            probes += 1;
+            probes == 3
+        };
+        let result = wake_until_up_with(&mut transport, Duration::from_secs(5), is_up);
+        assert!(matches!(result, Ok(Some(_))));
+        let sent = Packet::Udp {
+            destination: "127.0.0.1:9".parse().unwrap(),
+            payload: build_magic_packet(MAC).to_vec(),
+        };
+        assert_eq!(transport.sent(), [sent.clone(), sent.clone(), sent]);
+    }
+
+    #[test]
+    fn test_wake_until_up_deadline_and_send_errors() {
+        let mut transport = MockTransport::new();
+        let result = wake_until_up_with(&mut transport, Duration::from_millis(20), || false);
+        assert_eq!(result, Ok(None));
+        assert!(!transport.sent().is_empty());
+
+        let mut transport = MockTransport::new();
+        transport.fail_next(io::ErrorKind::PermissionDenied);
+        let result = wake_until_up_with(&mut transport, Duration::from_secs(5), || true);
+        assert!(result.is_err());
+        assert!(transport.sent().is_empty());
+    }
+
+    #[test]
+    fn test_subnet_broadcast_requires_udp() {
+        let args = |transport| {
+            Args::try_parse_from([
+                "wol",
+                "-m",
+                "00:11:22:33:44:55",
+                "-t",
+                transport,
+                "--subnet-broadcast",
+            ])
+            .unwrap()
+            .wake
+        };
+        assert!(args("udp").subnet_broadcast);
+        assert_eq!(
+            prepare_wake(&args("tcp"), None).err().unwrap(),
+            "--subnet-broadcast only applies to UDP; TCP can't reach a broadcast address"
+        );
+    }
+
+    #[test]
+    fn test_source_requires_udp() {
+        let args = |transport| {
+            Args::try_parse_from([
+                "wol",
+                "-m",
+                "00:11:22:33:44:55",
+                "-t",
+                transport,
+                "--source",
+                "127.0.0.1",
+            ])
+            .unwrap()
+            .wake
+        };
+        assert_eq!(args("udp").source, Some(Ipv4Addr::LOCALHOST.into()));
+        assert_eq!(
+            prepare_wake(&args("tcp"), None).err().unwrap(),
+            "--source only applies to UDP; the TCP transport can't choose a source address"
+        );
+    }
+
+    #[test]
+    fn test_send_udp_multicast_via_interface() {
+        let group = Ipv4Addr::new(239, 255, 77, 9);
+        let receiver = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        receiver
+            .join_multicast_v4(&group, &Ipv4Addr::LOCALHOST)
+            .unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let destination = SocketAddr::from((group, receiver.local_addr().unwrap().port()));
+        let options = UdpOptions {
+            multicast_ttl: Some(1),
+            multicast_interface: Some(Ipv4Addr::LOCALHOST),
+            unreachable_window: Some(Duration::from_secs(1)),
+            ..UdpOptions::default()
+        };
+        let packet = build_magic_packet(MAC);
+        let source = send_udp(&packet, destination, &options).unwrap();
+        assert_eq!(source.ip(), Ipv4Addr::LOCALHOST);
+
+        let mut buf = [0; 128];
+        let (len, _) = receiver.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], packet.as_slice());
+    }
+
+    #[test]
+    fn test_send_udp_from_source() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let options = UdpOptions {
+            source: Some(Ipv4Addr::LOCALHOST.into()),
+            ..UdpOptions::default()
+        };
+        let packet = build_magic_packet(MAC);
+        let source = send_udp(&packet, receiver.local_addr().unwrap(), &options).unwrap();
+        assert_eq!(source.ip(), Ipv4Addr::LOCALHOST);
+
+        let ipv6 = SocketAddr::from((Ipv6Addr::LOCALHOST, 9));
+        assert!(send_udp(&packet, ipv6, &options).is_err());
+    }
+
+    #[test]
+    fn test_send_udp_port_unreachable_counts_as_delivered() {
+        let closed = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let destination = closed.local_addr().unwrap();
+        drop(closed);
+        let options = UdpOptions {
+            unreachable_window: Some(Duration::from_millis(200)),
+            ..UdpOptions::default()
+        };
+        let packet = build_magic_packet(MAC);
+        assert!(send_udp(&packet, destination, &options).is_ok());
+    }
+
+    #[test]
+    fn test_send_udp_skips_watch_for_attached_broadcast() {
+        // The receiver never answers, so a watched send waits out the window.
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let options = UdpOptions {
+            unreachable_window: Some(Duration::from_secs(5)),
+            attached_broadcasts: vec![Ipv4Addr::LOCALHOST],
+            ..UdpOptions::default()
+        };
+        let start = Instant::now();
+        let packet = build_magic_packet(MAC);
+        assert!(send_udp(&packet, receiver.local_addr().unwrap(), &options).is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_cli_watch_unreachable_is_opt_in() {
+        let args = Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55"]).unwrap();
+        assert_eq!(
+            SystemTransport::new(&args.wake).options.unreachable_window,
+            None
+        );
+        let args = Args::try_parse_from(["wol", "-m", "00:11:22:33:44:55", "--watch-unreachable"])
+            .unwrap();
+        assert_eq!(
+            SystemTransport::new(&args.wake).options.unreachable_window,
+            Some(UNREACHABLE_WINDOW)
+        );
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        assert_eq!(latency_stats(&mut []), None);
+        let mut latencies: Vec<Duration> = [7, 3, 5, 1, 9, 2, 8, 4, 6, 10]
+            .map(Duration::from_secs)
+            .to_vec();
+        assert_eq!(
+            latency_stats(&mut latencies),
+            Some(LatencyStats {
+                min: Duration::from_secs(1),
+                median: Duration::from_secs(5),
+                p95: Duration::from_secs(10),
+            })
+        );
+        let one = Duration::from_millis(1500);
+        assert_eq!(
+            latency_stats(&mut [one]),
+            Some(LatencyStats {
+                min: one,
+                median: one,
+                p95: one,
+            })
+        );
+    }
 }
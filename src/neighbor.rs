@@ -0,0 +1,323 @@
+use crate::{import, route};
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PROC_ARP: &str = "/proc/net/arp";
+const ATF_COM: u32 = 0x02;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Networks larger than a /22 are not swept, to keep it to ~1000 datagrams.
+const MIN_SWEEP_PREFIX: u32 = 22;
+
+/// A resolved entry of the kernel's ARP/NDP neighbor table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbor {
+    pub ip: IpAddr,
+    pub mac: [u8; 6],
+    pub interface: String,
+}
+
+/// Reads the neighbor table with `ip neigh`, which covers IPv4 and IPv6,
+/// falling back to `/proc/net/arp` where iproute2 is missing, then to
+/// `arp -a` on macOS, the BSDs and Windows.
+pub fn table() -> io::Result<Vec<Neighbor>> {
+    let output = |program: &str, args: &[&str]| {
+        Command::new(program)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    if let Some(listing) = output("ip", &["neigh", "show"]) {
+        return Ok(parse_ip_neigh(&listing));
+    }
+    match fs::read_to_string(PROC_ARP) {
+        Ok(table) => Ok(parse_proc_arp(&table)),
+        Err(e) => match output("arp", &["-a"]) {
+            Some(listing) => Ok(parse_arp_a(&listing)
+                .into_iter()
+                .map(|(neighbor, _)| neighbor)
+                .collect()),
+            None => Err(io::Error::new(
+                e.kind(),
+                format!("ip neigh and arp -a failed and {PROC_ARP} is unreadable ({e})"),
+            )),
+        },
+    }
+}
+
+/// Parses `ip neigh show` output, skipping entries without a link-layer
+/// address (INCOMPLETE and FAILED ones).
+pub fn parse_ip_neigh(output: &str) -> Vec<Neighbor> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let ip = words.next()?.parse().ok()?;
+            let mut interface = None;
+            let mut mac = None;
+            while let Some(word) = words.next() {
+                match word {
+                    "dev" => interface = words.next(),
+                    "lladdr" => mac = words.next().and_then(import::parse_mac),
+                    _ => {}
+                }
+            }
+            Some(Neighbor {
+                ip,
+                mac: mac?,
+                interface: interface?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses `/proc/net/arp`, keeping only completed entries.
+pub fn parse_proc_arp(table: &str) -> Vec<Neighbor> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [ip, _, flags, mac, _, interface] = fields[..] else {
+                return None;
+            };
+            let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok()?;
+            if flags & ATF_COM == 0 {
+                return None;
+            }
+            Some(Neighbor {
+                ip: ip.parse().ok()?,
+                mac: import::parse_mac(mac)?,
+                interface: interface.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses `arp -a` as printed by Linux, the BSDs, macOS (`name (ip) at mac
+/// ... on interface`, with `?` for unnamed hosts) and Windows (`ip mac type`
+/// under an `Interface:` header naming the local address), with each host's
+/// name when given. MACs are parsed leniently, as macOS drops leading zeros.
+/// Incomplete entries and multicast or broadcast MACs are skipped.
+pub fn parse_arp_a(listing: &str) -> Vec<(Neighbor, Option<String>)> {
+    let mut windows_interface = "";
+    listing
+        .lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (name, ip, mac, interface) = match words[..] {
+                ["Interface:", local, ..] => {
+                    windows_interface = local;
+                    return None;
+                }
+                [name, ip, "at", mac, ref rest @ ..] => {
+                    let ip = ip.strip_prefix('(')?.strip_suffix(')')?;
+                    let interface = rest
+                        .iter()
+                        .skip_while(|word| **word != "on")
+                        .nth(1)
+                        .copied()
+                        .unwrap_or_default();
+                    ((name != "?").then(|| name.to_owned()), ip, mac, interface)
+                }
+                [ip, mac, _] => (None, ip, mac, windows_interface),
+                _ => return None,
+            };
+            let neighbor = Neighbor {
+                ip: ip.parse().ok()?,
+                mac: import::parse_mac(mac).filter(|mac| mac[0] & 0x01 == 0)?,
+                interface: interface.to_string(),
+            };
+            Some((neighbor, name))
+        })
+        .collect()
+}
+
+/// Sends an empty datagram to the discard port of `ip`, which makes the
+/// kernel resolve its MAC address if it isn't cached.
+pub fn nudge(ip: IpAddr) -> io::Result<()> {
+    let bind = if ip.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind)?;
+    socket.send_to(&[], SocketAddr::new(ip, 9))?;
+    Ok(())
+}
+
+/// Looks `ip` up in the neighbor table, nudging it and waiting up to
+/// `timeout` for the kernel to learn its MAC when it isn't cached yet.
+pub fn resolve(ip: IpAddr, timeout: Duration) -> io::Result<Option<Neighbor>> {
+    let find = || -> io::Result<Option<Neighbor>> {
+        Ok(table()?.into_iter().find(|neighbor| neighbor.ip == ip))
+    };
+    if let Some(neighbor) = find()? {
+        return Ok(Some(neighbor));
+    }
+    nudge(ip)?;
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        thread::sleep(POLL_INTERVAL);
+        if let Some(neighbor) = find()? {
+            return Ok(Some(neighbor));
+        }
+    }
+    Ok(None)
+}
+
+/// The directly attached, non-loopback networks small enough to sweep.
+pub fn sweepable(routes: &[route::Route]) -> Vec<&route::Route> {
+    routes
+        .iter()
+        .filter(|r| {
+            r.gateway.is_none()
+                && !r.destination.is_loopback()
+                && (MIN_SWEEP_PREFIX..31).contains(&r.prefix_len())
+        })
+        .collect()
+}
+
+/// Finds the neighbors currently holding `mac`. When none is cached, every
+/// host on `networks` is nudged and the table polled for up to `timeout`.
+pub fn find_mac(
+    mac: [u8; 6],
+    networks: &[&route::Route],
+    timeout: Duration,
+) -> io::Result<Vec<Neighbor>> {
+    let find = || -> io::Result<Vec<Neighbor>> {
+        Ok(table()?
+            .into_iter()
+            .filter(|neighbor| neighbor.mac == mac)
+            .collect())
+    };
+    let found = find()?;
+    if !found.is_empty() || networks.is_empty() {
+        return Ok(found);
+    }
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    for ip in networks.iter().flat_map(|network| network.hosts()) {
+        // Unreachable addresses fail individually; keep going.
+        let _ = socket.send_to(&[], SocketAddr::new(IpAddr::V4(ip), 9));
+    }
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        thread::sleep(POLL_INTERVAL);
+        let found = find()?;
+        if !found.is_empty() {
+            return Ok(found);
+        }
+    }
+    Ok(Vec::new())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub const IP_NEIGH: &str = "\
+192.168.1.40 dev eth0 lladdr b8:ae:ed:9c:c7:89 REACHABLE
+192.168.1.41 dev eth0 INCOMPLETE
+fe80::1 dev eth0 lladdr 00:11:22:33:44:55 router STALE
+";
+
+    pub const PROC_ARP: &str = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.40     0x1         0x2         b8:ae:ed:9c:c7:89     *        eth0
+192.168.1.41     0x1         0x0         00:00:00:00:00:00     *        eth0
+";
+
+    #[test]
+    fn test_parse_ip_neigh() {
+        assert_eq!(
+            parse_ip_neigh(IP_NEIGH),
+            [
+                Neighbor {
+                    ip: "192.168.1.40".parse().unwrap(),
+                    mac: [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+                    interface: String::from("eth0"),
+                },
+                Neighbor {
+                    ip: "fe80::1".parse().unwrap(),
+                    mac: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                    interface: String::from("eth0"),
+                },
+            ]
+        );
+    }
+
+    pub const ARP_A: &str = "\
+nas.lan (192.168.1.40) at b8:ae:ed:9c:c7:89 [ether] on eth0
+? (192.168.1.1) at 0:11:22:33:44:55 on en0 ifscope [ethernet]
+? (192.168.1.77) at (incomplete) on en0 ifscope [ethernet]
+";
+
+    pub const WINDOWS_ARP_A: &str = "\
+Interface: 192.168.1.5 --- 0x7
+  Internet Address      Physical Address      Type
+  192.168.1.40          b8-ae-ed-9c-c7-89     dynamic
+  192.168.1.255         ff-ff-ff-ff-ff-ff     static
+  224.0.0.22            01-00-5e-00-00-16     static
+";
+
+    #[test]
+    fn test_parse_arp_a() {
+        assert_eq!(
+            parse_arp_a(ARP_A),
+            [
+                (
+                    Neighbor {
+                        ip: "192.168.1.40".parse().unwrap(),
+                        mac: [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+                        interface: String::from("eth0"),
+                    },
+                    Some(String::from("nas.lan"))
+                ),
+                (
+                    Neighbor {
+                        ip: "192.168.1.1".parse().unwrap(),
+                        mac: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                        interface: String::from("en0"),
+                    },
+                    None
+                ),
+            ]
+        );
+        assert_eq!(
+            parse_arp_a(WINDOWS_ARP_A),
+            [(
+                Neighbor {
+                    ip: "192.168.1.40".parse().unwrap(),
+                    mac: [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+                    interface: String::from("192.168.1.5"),
+                },
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn test_sweepable() {
+        use route::tests::network;
+        let routes = [
+            network([0, 0, 0, 0], [0, 0, 0, 0], Some([192, 168, 1, 1])),
+            network([192, 168, 1, 0], [255, 255, 255, 0], None),
+            network([10, 0, 0, 0], [255, 0, 0, 0], None),
+            network([127, 0, 0, 0], [255, 0, 0, 0], None),
+        ];
+        assert_eq!(sweepable(&routes), [&routes[1]]);
+    }
+
+    #[test]
+    fn test_parse_proc_arp() {
+        assert_eq!(
+            parse_proc_arp(PROC_ARP),
+            [Neighbor {
+                ip: "192.168.1.40".parse().unwrap(),
+                mac: [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+                interface: String::from("eth0"),
+            }]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::dns;
+use std::net::Ipv4Addr;
+
+const TYPE_NB: u16 = 0x0020;
+const CLASS_IN: u16 = 1;
+
+/// NetBIOS names are at most 15 characters; the 16th byte is the service
+/// suffix, 0x00 for the workstation service every Windows machine registers.
+pub const MAX_NAME_LEN: usize = 15;
+
+/// Builds a broadcast NetBIOS name query (RFC 1002 section 4.2.12).
+pub fn query(id: u16, name: &str) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&id.to_be_bytes());
+    // Opcode query, recursion desired, broadcast.
+    message.extend_from_slice(&[0x01, 0x10]);
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    message.extend_from_slice(&encode_name(name));
+    message.extend_from_slice(&TYPE_NB.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    message
+}
+
+/// First-level encoding (RFC 1001 section 14.1): the name is uppercased,
+/// padded to 15 bytes with spaces, followed by the suffix byte, and each
+/// nibble is written as a letter from 'A'.
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut raw = [b' '; 16];
+    for (slot, byte) in raw.iter_mut().zip(name.bytes().take(MAX_NAME_LEN)) {
+        *slot = byte.to_ascii_uppercase();
+    }
+    raw[15] = 0x00;
+
+    let mut encoded = vec![32];
+    for byte in raw {
+        encoded.push(b'A' + (byte >> 4));
+        encoded.push(b'A' + (byte & 0x0F));
+    }
+    encoded.push(0);
+    encoded
+}
+
+/// Parses a positive name query response, returning the IPv4 addresses
+/// listed in its NB records.
+pub fn parse_response(message: &[u8], id: u16) -> Result<Vec<Ipv4Addr>, String> {
+    if message.len() < 12 {
+        return Err(String::from("truncated NetBIOS header"));
+    }
+    if u16::from_be_bytes([message[0], message[1]]) != id {
+        return Err(String::from("NetBIOS response ID does not match the query"));
+    }
+    if message[2] & 0x80 == 0 {
+        return Err(String::from("NetBIOS message is not a response"));
+    }
+    if message[3] & 0x0F != 0 {
+        return Err(format!("NetBIOS error code {}", message[3] & 0x0F));
+    }
+    let answers = u16::from_be_bytes([message[6], message[7]]);
+
+    let mut addresses = Vec::new();
+    let mut offset = 12;
+    for _ in 0..answers {
+        offset = dns::skip_name(message, offset)?;
+        let Some(fixed) = message.get(offset..offset + 10) else {
+            return Err(String::from("truncated NetBIOS answer"));
+        };
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let length = usize::from(u16::from_be_bytes([fixed[8], fixed[9]]));
+        offset += 10;
+        let Some(data) = message.get(offset..offset + length) else {
+            return Err(String::from("truncated NetBIOS record data"));
+        };
+        if rtype == TYPE_NB {
+            // Each entry is two bytes of NB flags followed by the address.
+            for entry in data.chunks_exact(6) {
+                addresses.push(Ipv4Addr::new(entry[2], entry[3], entry[4], entry[5]));
+            }
+        }
+        offset += length;
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_name() {
+        // The worked example from RFC 1001 section 14.1, with a 0x00 suffix.
+        let encoded = encode_name("Fred");
+        assert_eq!(encoded[0], 32);
+        assert_eq!(&encoded[1..31], b"EGFCEFEECACACACACACACACACACACA");
+        assert_eq!(&encoded[31..], b"AA\0");
+    }
+
+    #[test]
+    fn test_query_encoding() {
+        let message = query(0xBEEF, "gaming-pc");
+        assert_eq!(
+            &message[..12],
+            &[0xBE, 0xEF, 0x01, 0x10, 0, 1, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(message.len(), 12 + 34 + 4);
+        assert_eq!(&message[46..], &[0x00, 0x20, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let mut message = vec![0xBE, 0xEF, 0x85, 0x00, 0, 0, 0, 1, 0, 0, 0, 0];
+        message.extend_from_slice(&encode_name("GAMING-PC"));
+        message.extend_from_slice(&[0x00, 0x20, 0x00, 0x01, 0, 0, 0x0b, 0xb8, 0, 6]);
+        message.extend_from_slice(&[0x00, 0x00, 192, 168, 1, 50]);
+        assert_eq!(
+            parse_response(&message, 0xBEEF).unwrap(),
+            [Ipv4Addr::new(192, 168, 1, 50)]
+        );
+        assert!(parse_response(&message, 0x1234).is_err());
+        message[3] = 0x03;
+        assert!(parse_response(&message, 0xBEEF).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// Checks whether a host is awake by connecting to a TCP port. A refused
+/// connection counts too: only a running network stack sends the reset.
+pub fn tcp(addr: SocketAddr, timeout: Duration) -> bool {
+    match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(_) => true,
+        Err(e) => e.kind() == io::ErrorKind::ConnectionRefused,
+    }
+}
+
+/// Checks whether a service is ready at any of `addrs`, which unlike
+/// [`host`] needs the connection to be accepted.
+pub fn accepting(addrs: &[SocketAddr], timeout: Duration) -> bool {
+    addrs
+        .iter()
+        .any(|addr| TcpStream::connect_timeout(addr, timeout).is_ok())
+}
+
+/// Checks whether any of a host's addresses is awake. The addresses are
+/// resolved by the caller, once: a sleeping host may not answer name
+/// queries, and a slow lookup on every probe would stretch the poll.
+pub fn host(addrs: &[SocketAddr], timeout: Duration) -> bool {
+    addrs.iter().any(|addr| tcp(*addr, timeout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_tcp_listening_and_refused_are_up() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(tcp(addr, Duration::from_secs(1)));
+        assert!(host(&[addr], Duration::from_secs(1)));
+        assert!(accepting(&[addr], Duration::from_secs(1)));
+        drop(listener);
+        assert!(tcp(addr, Duration::from_secs(1)));
+        assert!(!accepting(&[addr], Duration::from_secs(1)));
+        assert!(!host(&[], Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,107 @@
+use std::io;
+use std::process::Command;
+
+/// A remote-desktop protocol with a client to hand the host over to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Rdp,
+    Vnc,
+}
+
+impl Protocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            Protocol::Rdp => "RDP",
+            Protocol::Vnc => "VNC",
+        }
+    }
+
+    pub fn default_port(self) -> u16 {
+        match self {
+            Protocol::Rdp => 3389,
+            Protocol::Vnc => 5900,
+        }
+    }
+}
+
+/// Formats `host:port`, bracketing IPv6 literals.
+fn endpoint(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+/// Clients to try in order, as program and arguments.
+fn clients(protocol: Protocol, host: &str, port: u16) -> Vec<Vec<String>> {
+    let endpoint = endpoint(host, port);
+    let url = format!("{}://{endpoint}", protocol.name().to_ascii_lowercase());
+    // vncviewer reads `host::port` as a port rather than a display number.
+    let vncviewer = vec![String::from("vncviewer"), format!("{host}::{port}")];
+    if cfg!(target_os = "macos") {
+        match protocol {
+            Protocol::Rdp => vec![vec![
+                String::from("open"),
+                format!("rdp://full%20address=s:{endpoint}"),
+            ]],
+            Protocol::Vnc => vec![vec![String::from("open"), url]],
+        }
+    } else if cfg!(windows) {
+        match protocol {
+            Protocol::Rdp => vec![vec![String::from("mstsc"), format!("/v:{endpoint}")]],
+            Protocol::Vnc => vec![vncviewer],
+        }
+    } else {
+        let first = match protocol {
+            Protocol::Rdp => vec![String::from("xfreerdp"), format!("/v:{endpoint}")],
+            Protocol::Vnc => vncviewer,
+        };
+        vec![
+            first,
+            vec![String::from("remmina"), String::from("-c"), url],
+        ]
+    }
+}
+
+/// Starts the platform's client for `protocol` pointed at the host, without
+/// waiting for it to exit.
+pub fn launch(protocol: Protocol, host: &str, port: u16) -> Result<String, String> {
+    let mut tried = Vec::new();
+    for client in clients(protocol, host, port) {
+        match Command::new(&client[0]).args(&client[1..]).spawn() {
+            Ok(_) => return Ok(client[0].clone()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => tried.push(client[0].clone()),
+            Err(e) => return Err(format!("failed to start {}: {e}", client[0])),
+        }
+    }
+    Err(format!(
+        "no {} client found (tried {})",
+        protocol.name(),
+        tried.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_brackets_ipv6() {
+        assert_eq!(endpoint("desktop.lan", 3389), "desktop.lan:3389");
+        assert_eq!(endpoint("fe80::1", 5900), "[fe80::1]:5900");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_clients_linux() {
+        assert_eq!(
+            clients(Protocol::Vnc, "desktop.lan", 5901)[0],
+            ["vncviewer", "desktop.lan::5901"]
+        );
+        assert_eq!(
+            clients(Protocol::Rdp, "10.0.0.5", 3389)[1],
+            ["remmina", "-c", "rdp://10.0.0.5:3389"]
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use crate::{dns, netbios};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MDNS_GROUP: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+const LLMNR_GROUP: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 252)), 5355);
+const NETBIOS_BROADCAST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), 137);
+const MDNS_TIMEOUT: Duration = Duration::from_secs(2);
+const LINK_LOCAL_TIMEOUT: Duration = Duration::from_secs(1);
+const SYSTEM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An address family to put first when a name has both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+/// How names are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub prefer: Option<Family>,
+    /// How long to wait for the system resolver, or for `server`.
+    pub timeout: Duration,
+    /// A DNS server to ask instead of the system resolver.
+    pub server: Option<SocketAddr>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            prefer: None,
+            timeout: SYSTEM_TIMEOUT,
+            server: None,
+        }
+    }
+}
+
+/// Resolves a destination host to socket addresses with the default options.
+pub fn resolve(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    resolve_with(host, port, &Options::default())
+}
+
+/// Resolves a destination host to socket addresses. IP literals are used as
+/// is and names go to the system resolver (or `options.server`) first. When
+/// it fails, `.local` names are queried over multicast DNS, and single-label
+/// names (as Windows machines outside DNS use) over LLMNR and then NetBIOS.
+pub fn resolve_with(host: &str, port: u16, options: &Options) -> Result<Vec<SocketAddr>, String> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let lookup = match options.server {
+        Some(server) => server_lookup(server, host, options.timeout).map(|ips| {
+            ips.into_iter()
+                .map(|ip| SocketAddr::new(ip, port))
+                .collect()
+        }),
+        None => system_lookup(host, port, options.timeout),
+    };
+    let system_error = match lookup {
+        Ok(addrs) if !addrs.is_empty() => return Ok(order(addrs, options.prefer)),
+        Ok(_) => String::from("no addresses found"),
+        Err(e) => e,
+    };
+
+    let addresses = if is_mdns_name(host) {
+        mdns(host, MDNS_TIMEOUT).map_err(|e| format!("mDNS query for {host} failed: {e}"))?
+    } else if is_single_label(host) {
+        let mut addresses = llmnr(host, LINK_LOCAL_TIMEOUT)
+            .map_err(|e| format!("LLMNR query for {host} failed: {e}"))?;
+        if addresses.is_empty() && host.len() <= netbios::MAX_NAME_LEN {
+            addresses = netbios(host, LINK_LOCAL_TIMEOUT)
+                .map_err(|e| format!("NetBIOS query for {host} failed: {e}"))?;
+        }
+        addresses
+    } else {
+        return Err(format!("failed to resolve {host}: {system_error}"));
+    };
+    if addresses.is_empty() {
+        return Err(format!(
+            "failed to resolve {host}: {system_error}, and no local name service answered"
+        ));
+    }
+    let addrs = addresses
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect();
+    Ok(order(addrs, options.prefer))
+}
+
+/// Runs the system resolver on a separate thread so a stuck lookup can be
+/// abandoned after `timeout`.
+fn system_lookup(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>, String> {
+    let (sender, receiver) = mpsc::channel();
+    let name = host.to_string();
+    thread::spawn(move || {
+        let result = (name.as_str(), port)
+            .to_socket_addrs()
+            .map(Iterator::collect::<Vec<_>>);
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(addrs)) => Ok(addrs),
+        Ok(Err(e)) if is_no_such_host(&e.to_string()) => {
+            Err(String::from("no such host (NXDOMAIN)"))
+        }
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!(
+            "the system resolver did not answer within {}s",
+            timeout.as_secs_f32()
+        )),
+    }
+}
+
+/// Asks the DNS server at `server` for the A and AAAA records of `name`, one
+/// query each since servers rarely answer several questions at once.
+fn server_lookup(server: SocketAddr, name: &str, timeout: Duration) -> Result<Vec<IpAddr>, String> {
+    let bind: SocketAddr = if server.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(bind)
+        .and_then(|socket| socket.connect(server).map(|()| socket))
+        .map_err(|e| format!("failed to reach DNS server {server}: {e}"))?;
+    let deadline = Instant::now() + timeout;
+    let mut addresses = Vec::new();
+    for qtype in [dns::TYPE_A, dns::TYPE_AAAA] {
+        let id = query_id();
+        socket
+            .send(&dns::query(id, name, &[qtype], true))
+            .map_err(|e| format!("failed to query DNS server {server}: {e}"))?;
+        let mut buf = [0; 1500];
+        let response = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(format!(
+                    "DNS server {server} did not answer within {}s",
+                    timeout.as_secs_f32()
+                ));
+            }
+            socket
+                .set_read_timeout(Some(remaining))
+                .map_err(|e| e.to_string())?;
+            match socket.recv(&mut buf) {
+                Ok(len) => {
+                    if let Ok(response) = dns::parse_response(&buf[..len], id) {
+                        break response;
+                    }
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => return Err(format!("failed to query DNS server {server}: {e}")),
+            }
+        };
+        match response.rcode {
+            0 => addresses.extend(response.addresses),
+            dns::RCODE_NXDOMAIN => return Err(String::from("no such host (NXDOMAIN)")),
+            rcode => return Err(format!("DNS server {server} answered with rcode {rcode}")),
+        }
+    }
+    Ok(addresses)
+}
+
+/// Recognizes the getaddrinfo errors for a name that does not exist, as
+/// worded by glibc, musl, macOS and Windows.
+fn is_no_such_host(message: &str) -> bool {
+    [
+        "Name or service not known",
+        "Name does not resolve",
+        "nodename nor servname provided",
+        "No such host is known",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Moves the preferred family to the front, keeping the resolver's order
+/// otherwise.
+fn order(mut addrs: Vec<SocketAddr>, prefer: Option<Family>) -> Vec<SocketAddr> {
+    if let Some(prefer) = prefer {
+        addrs.sort_by_key(|addr| (addr.is_ipv4() != (prefer == Family::V4)) as u8);
+    }
+    addrs
+}
+
+/// Reorders addresses to alternate between families, starting with the
+/// family of the first, as Happy Eyeballs (RFC 8305) does.
+pub fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return Vec::new();
+    };
+    let (mut same, mut other): (Vec<_>, Vec<_>) = addrs
+        .iter()
+        .partition(|addr| addr.is_ipv4() == first.is_ipv4());
+    let mut out = Vec::with_capacity(addrs.len());
+    same.reverse();
+    other.reverse();
+    while let Some(addr) = same.pop() {
+        out.push(addr);
+        out.extend(other.pop());
+    }
+    out.extend(other.into_iter().rev());
+    out
+}
+
+/// Resolves a `host`, `host:port` or `[v6]:port` endpoint with `options`,
+/// using `default_port` when none is given.
+pub fn resolve_endpoint(
+    endpoint: &str,
+    default_port: u16,
+    options: &Options,
+) -> Result<Vec<SocketAddr>, String> {
+    if let Ok(addr) = endpoint.parse::<SocketAddr>() {
+        return Ok(vec![addr]);
+    }
+    if let Some((host, port)) = endpoint.rsplit_once(':')
+        && !host.contains(':')
+    {
+        let port = port
+            .parse()
+            .map_err(|_| format!("invalid port in {endpoint}"))?;
+        return resolve_with(host, port, options);
+    }
+    let host = endpoint.trim_start_matches('[').trim_end_matches(']');
+    resolve_with(host, default_port, options)
+}
+
+pub fn is_mdns_name(host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    host.len() > ".local".len()
+        && host[host.len() - ".local".len()..].eq_ignore_ascii_case(".local")
+}
+
+fn is_single_label(host: &str) -> bool {
+    !host.is_empty() && !host.contains('.')
+}
+
+/// Picks an unpredictable ID for a query that will be sent over UDP.
+pub fn query_id() -> u16 {
+    RandomState::new().hash_one(Instant::now()) as u16
+}
+
+/// Sends a one-shot (legacy unicast) mDNS query for the A and AAAA records of
+/// `name`, returning the addresses from the first response that has any.
+pub fn mdns(name: &str, timeout: Duration) -> io::Result<Vec<IpAddr>> {
+    let id = query_id();
+    let query = dns::query(id, name, &[dns::TYPE_A, dns::TYPE_AAAA], false);
+    exchange(MDNS_GROUP, &query, timeout, |message| {
+        dns::parse_response(message, id).ok().map(|r| r.addresses)
+    })
+}
+
+/// Sends an LLMNR query (RFC 4795). Responders answer one record type per
+/// question, so only A records are asked for.
+pub fn llmnr(name: &str, timeout: Duration) -> io::Result<Vec<IpAddr>> {
+    let id = query_id();
+    let query = dns::query(id, name, &[dns::TYPE_A], false);
+    exchange(LLMNR_GROUP, &query, timeout, |message| {
+        dns::parse_response(message, id).ok().map(|r| r.addresses)
+    })
+}
+
+/// Broadcasts a NetBIOS name query for the workstation service of `name`.
+pub fn netbios(name: &str, timeout: Duration) -> io::Result<Vec<IpAddr>> {
+    let id = query_id();
+    let query = netbios::query(id, name);
+    exchange(NETBIOS_BROADCAST, &query, timeout, |message| {
+        netbios::parse_response(message, id)
+            .ok()
+            .map(|addresses| addresses.into_iter().map(IpAddr::V4).collect())
+    })
+}
+
+/// Sends `query` to `destination` and waits up to `timeout` for a reply that
+/// `parse` turns into at least one address. IPv6 link-local answers are
+/// skipped since they carry no scope to send on.
+fn exchange(
+    destination: SocketAddr,
+    query: &[u8],
+    timeout: Duration,
+    parse: impl Fn(&[u8]) -> Option<Vec<IpAddr>>,
+) -> io::Result<Vec<IpAddr>> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_broadcast(true)?;
+    socket.send_to(query, destination)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(Vec::new());
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match socket.recv_from(&mut buf) {
+            Ok((len, _)) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(e),
+        };
+        let Some(addresses) = parse(&buf[..len]) else {
+            continue;
+        };
+        let addresses: Vec<IpAddr> = addresses
+            .into_iter()
+            .filter(|ip| !matches!(ip, IpAddr::V6(v6) if v6.is_unicast_link_local()))
+            .collect();
+        if !addresses.is_empty() {
+            return Ok(addresses);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_ip_literal() {
+        assert_eq!(
+            resolve("192.168.1.255", 9).unwrap(),
+            ["192.168.1.255:9".parse().unwrap()]
+        );
+        assert_eq!(
+            resolve("ff02::1", 9).unwrap(),
+            ["[ff02::1]:9".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_order_prefers_family() {
+        let addrs: Vec<SocketAddr> = ["[2001:db8::1]:9", "192.0.2.1:9", "[2001:db8::2]:9"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        assert_eq!(order(addrs.clone(), None), addrs);
+        assert_eq!(
+            order(addrs.clone(), Some(Family::V4)),
+            [addrs[1], addrs[0], addrs[2]]
+        );
+        assert_eq!(
+            order(addrs.clone(), Some(Family::V6)),
+            [addrs[0], addrs[2], addrs[1]]
+        );
+    }
+
+    #[test]
+    fn test_interleave() {
+        let addrs: Vec<SocketAddr> = [
+            "[2001:db8::1]:9",
+            "[2001:db8::2]:9",
+            "[2001:db8::3]:9",
+            "192.0.2.1:9",
+        ]
+        .iter()
+        .map(|a| a.parse().unwrap())
+        .collect();
+        assert_eq!(interleave(&addrs), [addrs[0], addrs[3], addrs[1], addrs[2]]);
+        assert_eq!(interleave(&addrs[3..]), [addrs[3]]);
+        assert_eq!(
+            interleave(&[addrs[3], addrs[0], addrs[1]]),
+            [addrs[3], addrs[0], addrs[1]]
+        );
+        assert!(interleave(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_is_no_such_host() {
+        assert!(is_no_such_host(
+            "failed to lookup address information: Name or service not known"
+        ));
+        assert!(!is_no_such_host(
+            "failed to lookup address information: Temporary failure in name resolution"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_endpoint() {
+        let resolved = |endpoint| resolve_endpoint(endpoint, 161, &Options::default()).unwrap();
+        assert_eq!(resolved("10.0.0.2"), ["10.0.0.2:161".parse().unwrap()]);
+        assert_eq!(
+            resolved("10.0.0.2:1161"),
+            ["10.0.0.2:1161".parse().unwrap()]
+        );
+        assert_eq!(
+            resolved("[fe80::1]:1161"),
+            ["[fe80::1]:1161".parse().unwrap()]
+        );
+        assert_eq!(resolved("fe80::1"), ["[fe80::1]:161".parse().unwrap()]);
+        assert!(resolve_endpoint("10.0.0.2:x", 161, &Options::default()).is_err());
+    }
+
+    #[test]
+    fn test_is_mdns_name() {
+        assert!(is_mdns_name("nas.local"));
+        assert!(is_mdns_name("NAS.Local."));
+        assert!(!is_mdns_name(".local"));
+        assert!(!is_mdns_name("nas.lan"));
+        assert!(!is_mdns_name("nas"));
+    }
+
+    #[test]
+    fn test_is_single_label() {
+        assert!(is_single_label("GAMING-PC"));
+        assert!(!is_single_label("nas.lan"));
+        assert!(!is_single_label(""));
+    }
+
+    /// Answers A queries for any name with `address`, and everything else
+    /// with no records, or NXDOMAIN when `rcode` says so.
+    fn dns_server(address: [u8; 4], rcode: u8, queries: usize) -> SocketAddr {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = [0; 512];
+            for _ in 0..queries {
+                let (len, from) = server.recv_from(&mut buf).unwrap();
+                let mut reply = buf[..len].to_vec();
+                let is_a = reply[len - 4..len - 2] == dns::TYPE_A.to_be_bytes();
+                reply[2] = 0x81;
+                reply[3] = 0x80 | rcode;
+                if is_a && rcode == 0 {
+                    reply[7] = 1;
+                    reply.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+                    reply.extend_from_slice(&address);
+                }
+                server.send_to(&reply, from).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_server_lookup() {
+        let server = dns_server([10, 8, 0, 5], 0, 2);
+        assert_eq!(
+            server_lookup(server, "build.corp.example", Duration::from_secs(5)),
+            Ok(vec!["10.8.0.5".parse().unwrap()])
+        );
+        let options = Options {
+            server: Some(dns_server([10, 8, 0, 5], 0, 2)),
+            ..Options::default()
+        };
+        assert_eq!(
+            resolve_with("build.corp.example", 9, &options),
+            Ok(vec!["10.8.0.5:9".parse().unwrap()])
+        );
+    }
+
+    #[test]
+    fn test_server_lookup_nxdomain() {
+        let server = dns_server([0; 4], dns::RCODE_NXDOMAIN, 1);
+        assert_eq!(
+            server_lookup(server, "missing.corp.example", Duration::from_secs(5)),
+            Err(String::from("no such host (NXDOMAIN)"))
+        );
+    }
+
+    #[test]
+    fn test_exchange_returns_first_parsed_reply() {
+        let responder = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let destination = responder.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut buf = [0; 64];
+            let (_, from) = responder.recv_from(&mut buf).unwrap();
+            responder.send_to(b"junk", from).unwrap();
+            responder.send_to(b"ok", from).unwrap();
+        });
+
+        let addresses = exchange(destination, b"query", Duration::from_secs(5), |reply| {
+            (reply == b"ok").then(|| vec!["192.168.1.50".parse().unwrap()])
+        })
+        .unwrap();
+        handle.join().unwrap();
+        assert_eq!(addresses, ["192.168.1.50".parse::<IpAddr>().unwrap()]);
+    }
+}
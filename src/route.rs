@@ -0,0 +1,357 @@
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::process::Command;
+
+const PROC_ROUTE: &str = "/proc/net/route";
+const RTF_UP: u16 = 0x0001;
+
+/// One entry of the kernel's IPv4 routing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub interface: String,
+    pub destination: Ipv4Addr,
+    pub mask: Ipv4Addr,
+    pub gateway: Option<Ipv4Addr>,
+    pub metric: u32,
+}
+
+impl Route {
+    pub fn prefix_len(&self) -> u32 {
+        u32::from(self.mask).leading_ones()
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & u32::from(self.mask) == u32::from(self.destination)
+    }
+
+    /// The directed broadcast address of the route's network.
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.destination) | !u32::from(self.mask))
+    }
+
+    /// The usable host addresses of the route's network, without the network
+    /// and broadcast addresses.
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let network = u32::from(self.destination);
+        (network.saturating_add(1)..u32::from(self.broadcast())).map(Ipv4Addr::from)
+    }
+}
+
+/// Reads the IPv4 routing table from `/proc/net/route` on Linux, falling back
+/// to `netstat -rn` on macOS, the BSDs and Windows.
+pub fn table() -> io::Result<Vec<Route>> {
+    match fs::read_to_string(PROC_ROUTE) {
+        Ok(table) => Ok(parse_table(&table)),
+        Err(e) => match Command::new("netstat").arg("-rn").output() {
+            Ok(output) if output.status.success() => {
+                Ok(parse_netstat(&String::from_utf8_lossy(&output.stdout)))
+            }
+            _ => Err(io::Error::new(
+                e.kind(),
+                format!("{PROC_ROUTE} is unreadable ({e}) and netstat -rn failed"),
+            )),
+        },
+    }
+}
+
+/// Parses `/proc/net/route`, skipping routes that are down. The kernel prints
+/// addresses as the raw in-memory u32, so they are decoded in native order.
+pub fn parse_table(table: &str) -> Vec<Route> {
+    let address = |field: &str| {
+        u32::from_str_radix(field, 16)
+            .ok()
+            .map(|raw| Ipv4Addr::from(raw.to_ne_bytes()))
+    };
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [
+                interface,
+                destination,
+                gateway,
+                flags,
+                _,
+                _,
+                metric,
+                mask,
+                ..,
+            ] = fields[..]
+            else {
+                return None;
+            };
+            if u16::from_str_radix(flags, 16).ok()? & RTF_UP == 0 {
+                return None;
+            }
+            let gateway = address(gateway)?;
+            Some(Route {
+                interface: interface.to_string(),
+                destination: address(destination)?,
+                mask: address(mask)?,
+                gateway: (!gateway.is_unspecified()).then_some(gateway),
+                metric: metric.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Parses the IPv4 routes of `netstat -rn`, as printed by macOS and the BSDs
+/// (`Destination Gateway Flags Netif`) or Windows (`Network Destination
+/// Netmask Gateway Interface Metric`). Windows names interfaces by their
+/// address, and neither gives metrics for the BSD form, which count as 0.
+pub fn parse_netstat(output: &str) -> Vec<Route> {
+    let mut windows = false;
+    let mut ipv6 = false;
+    let mut routes = Vec::new();
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            ["Network", "Destination", ..] => windows = true,
+            ["Internet:"] => ipv6 = false,
+            ["Internet6:"] => ipv6 = true,
+            [destination, mask, gateway, interface, metric] if windows => {
+                let (Ok(destination), Ok(mask), Ok(metric)) =
+                    (destination.parse(), mask.parse(), metric.parse())
+                else {
+                    continue;
+                };
+                routes.push(Route {
+                    interface: interface.to_string(),
+                    destination,
+                    mask,
+                    gateway: gateway.parse().ok(),
+                    metric,
+                });
+            }
+            [destination, gateway, flags, interface, ..]
+                if !windows && !ipv6 && flags.contains('U') =>
+            {
+                let Some((destination, prefix_len)) = parse_bsd_destination(destination, flags)
+                else {
+                    continue;
+                };
+                let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+                routes.push(Route {
+                    interface: interface.to_string(),
+                    destination,
+                    mask: mask.into(),
+                    // On-link routes name a link (`link#6`) or a MAC instead.
+                    gateway: gateway.parse().ok().filter(|_| flags.contains('G')),
+                    metric: 0,
+                });
+            }
+            _ => {}
+        }
+    }
+    routes
+}
+
+/// Parses a BSD route destination: `default`, `a.b.c.d/len`, a network with
+/// its trailing zero octets left off (`192.168.1`), or a host when `flags`
+/// has `H`.
+fn parse_bsd_destination(destination: &str, flags: &str) -> Option<(Ipv4Addr, u32)> {
+    if destination == "default" {
+        return Some((Ipv4Addr::UNSPECIFIED, 0));
+    }
+    let (address, prefix_len) = match destination.split_once('/') {
+        Some((address, len)) => (address, Some(len.parse().ok().filter(|len| *len <= 32)?)),
+        None => (destination, None),
+    };
+    let mut octets = [0; 4];
+    let mut given = 0u32;
+    for (octet, part) in octets.iter_mut().zip(address.split('.')) {
+        *octet = part.parse().ok()?;
+        given += 1;
+    }
+    if address.split('.').count() != given as usize {
+        return None;
+    }
+    let prefix_len = match prefix_len {
+        Some(len) => len,
+        None if flags.contains('H') => 32,
+        None => given * 8,
+    };
+    Some((octets.into(), prefix_len))
+}
+
+/// Picks the route the kernel would use for `ip`: the longest matching
+/// prefix, then the lowest metric.
+pub fn lookup(routes: &[Route], ip: Ipv4Addr) -> Option<&Route> {
+    routes
+        .iter()
+        .filter(|route| route.contains(ip))
+        .max_by(|a, b| {
+            a.prefix_len()
+                .cmp(&b.prefix_len())
+                .then(b.metric.cmp(&a.metric))
+        })
+}
+
+/// Asks the kernel which local address it would send from to reach
+/// `destination`, without sending anything.
+pub fn source_address(destination: SocketAddr) -> io::Result<IpAddr> {
+    let bind = if destination.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.set_broadcast(true)?;
+    socket.connect(destination)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// An eth0 route with metric 0, for tests across the crate.
+    pub fn network(destination: [u8; 4], mask: [u8; 4], gateway: Option<[u8; 4]>) -> Route {
+        Route {
+            interface: String::from("eth0"),
+            destination: destination.into(),
+            mask: mask.into(),
+            gateway: gateway.map(Into::into),
+            metric: 0,
+        }
+    }
+
+    const TABLE: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+eth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+eth1\t00000A0A\t00000000\t0000\t0\t0\t0\t0000FFFF\t0\t0\t0
+";
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn test_parse_table() {
+        let routes = parse_table(TABLE);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(
+            routes[0],
+            Route {
+                interface: String::from("eth0"),
+                destination: Ipv4Addr::UNSPECIFIED,
+                mask: Ipv4Addr::UNSPECIFIED,
+                gateway: Some(Ipv4Addr::new(192, 168, 1, 1)),
+                metric: 100,
+            }
+        );
+        assert_eq!(routes[2].destination, Ipv4Addr::new(192, 168, 1, 0));
+        assert_eq!(routes[2].prefix_len(), 24);
+        assert_eq!(routes[2].gateway, None);
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn test_lookup() {
+        let routes = parse_table(TABLE);
+        let on_link = lookup(&routes, Ipv4Addr::new(192, 168, 1, 255)).unwrap();
+        assert_eq!(
+            (on_link.interface.as_str(), on_link.prefix_len()),
+            ("eth0", 24)
+        );
+        let default = lookup(&routes, Ipv4Addr::BROADCAST).unwrap();
+        assert_eq!((default.interface.as_str(), default.metric), ("eth0", 100));
+        // The down route to 10.10.0.0/16 is ignored.
+        assert_eq!(
+            lookup(&routes, Ipv4Addr::new(10, 10, 0, 5))
+                .unwrap()
+                .prefix_len(),
+            0
+        );
+        assert_eq!(lookup(&[], Ipv4Addr::BROADCAST), None);
+    }
+
+    #[test]
+    fn test_parse_netstat_bsd() {
+        let output = "\
+Routing tables
+
+Internet:
+Destination        Gateway            Flags               Netif Expire
+default            192.168.1.1        UGScg                 en0
+127                127.0.0.1          UCS                   lo0
+192.168.1          link#6             UCS                   en0      !
+192.168.1.40       b8:ae:ed:9c:c7:89  UHLWIi                en0   1177
+10.8.0.0/16        10.8.0.1           UGSc                  utun3
+
+Internet6:
+Destination        Gateway            Flags               Netif Expire
+default            fe80::%utun0       UGcIg               utun0
+";
+        let routes = parse_netstat(output);
+        assert_eq!(routes.len(), 5);
+        assert_eq!(
+            routes[0],
+            Route {
+                interface: String::from("en0"),
+                destination: Ipv4Addr::UNSPECIFIED,
+                mask: Ipv4Addr::UNSPECIFIED,
+                gateway: Some(Ipv4Addr::new(192, 168, 1, 1)),
+                metric: 0,
+            }
+        );
+        assert_eq!(routes[1].prefix_len(), 8);
+        assert_eq!(
+            routes[2],
+            Route {
+                interface: String::from("en0"),
+                ..network([192, 168, 1, 0], [255, 255, 255, 0], None)
+            }
+        );
+        assert_eq!(routes[3].prefix_len(), 32);
+        assert_eq!(routes[3].gateway, None);
+        assert_eq!(routes[4].destination, Ipv4Addr::new(10, 8, 0, 0));
+        assert_eq!(routes[4].prefix_len(), 16);
+    }
+
+    #[test]
+    fn test_parse_netstat_windows() {
+        let output = "\
+IPv4 Route Table
+===========================================================================
+Active Routes:
+Network Destination        Netmask          Gateway       Interface  Metric
+          0.0.0.0          0.0.0.0      192.168.1.1    192.168.1.5     25
+      192.168.1.0    255.255.255.0         On-link     192.168.1.5    281
+===========================================================================
+Persistent Routes:
+  None
+";
+        let routes = parse_netstat(output);
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].gateway, Some(Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(routes[0].metric, 25);
+        assert_eq!(
+            routes[1],
+            Route {
+                interface: String::from("192.168.1.5"),
+                destination: Ipv4Addr::new(192, 168, 1, 0),
+                mask: Ipv4Addr::new(255, 255, 255, 0),
+                gateway: None,
+                metric: 281,
+            }
+        );
+    }
+
+    #[test]
+    fn test_hosts() {
+        let route = network([192, 168, 1, 0], [255, 255, 255, 252], None);
+        assert_eq!(
+            route.hosts().collect::<Vec<_>>(),
+            [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)]
+        );
+        assert_eq!(route.broadcast(), Ipv4Addr::new(192, 168, 1, 3));
+    }
+
+    #[test]
+    fn test_source_address_loopback() {
+        let source = source_address("127.0.0.1:9".parse().unwrap()).unwrap();
+        assert!(source.is_loopback());
+    }
+}
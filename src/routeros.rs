@@ -0,0 +1,254 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// The plain-text RouterOS API port. Everything on it, the login password
+/// included, crosses the network in the clear: the TLS API on 8729 isn't
+/// supported, so reach the router over a trusted link or a tunnel.
+pub const DEFAULT_PORT: u16 = 8728;
+
+/// The longest word sent or accepted. Replies to `/login` and `/tool/wol`
+/// are short, so anything longer is a broken or hostile peer.
+const MAX_WORD_LENGTH: usize = 64 * 1024;
+
+/// A connection to the MikroTik RouterOS API, which exchanges "sentences" of
+/// length-prefixed words terminated by an empty word.
+pub struct Client {
+    stream: TcpStream,
+}
+
+fn encode_length(length: u32, out: &mut Vec<u8>) {
+    match length {
+        0..0x80 => out.push(length as u8),
+        0x80..0x4000 => out.extend_from_slice(&(length | 0x8000).to_be_bytes()[2..]),
+        0x4000..0x20_0000 => out.extend_from_slice(&(length | 0xC0_0000).to_be_bytes()[1..]),
+        0x20_0000..0x1000_0000 => out.extend_from_slice(&(length | 0xE000_0000).to_be_bytes()),
+        _ => {
+            out.push(0xF0);
+            out.extend_from_slice(&length.to_be_bytes());
+        }
+    }
+}
+
+fn read_length(reader: &mut impl Read) -> io::Result<usize> {
+    let mut first = [0; 1];
+    reader.read_exact(&mut first)?;
+    let first = first[0];
+    let (extra, initial) = match first {
+        0x00..0x80 => (0, u32::from(first)),
+        0x80..0xC0 => (1, u32::from(first & 0x3F)),
+        0xC0..0xE0 => (2, u32::from(first & 0x1F)),
+        0xE0..0xF0 => (3, u32::from(first & 0x0F)),
+        _ => (4, 0),
+    };
+    let mut rest = [0; 4];
+    reader.read_exact(&mut rest[..extra])?;
+    Ok(rest[..extra]
+        .iter()
+        .fold(initial, |acc, &b| (acc << 8) | u32::from(b)) as usize)
+}
+
+pub fn encode_sentence(words: &[&str]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for word in words {
+        let length = u32::try_from(word.len())
+            .ok()
+            .filter(|_| word.len() <= MAX_WORD_LENGTH)
+            .ok_or_else(|| too_long(word.len()))?;
+        encode_length(length, &mut out);
+        out.extend_from_slice(word.as_bytes());
+    }
+    out.push(0);
+    Ok(out)
+}
+
+fn too_long(length: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("a {length}-byte word exceeds the {MAX_WORD_LENGTH}-byte limit"),
+    )
+}
+
+pub fn read_sentence(reader: &mut impl Read) -> io::Result<Vec<String>> {
+    let mut words = Vec::new();
+    loop {
+        let length = read_length(reader)?;
+        if length == 0 {
+            return Ok(words);
+        }
+        if length > MAX_WORD_LENGTH {
+            return Err(too_long(length));
+        }
+        let mut word = vec![0; length];
+        reader.read_exact(&mut word)?;
+        words.push(String::from_utf8_lossy(&word).into_owned());
+    }
+}
+
+/// Looks up an `=key=value` attribute word in a reply sentence.
+fn attribute<'a>(sentence: &'a [String], key: &str) -> Option<&'a str> {
+    sentence
+        .iter()
+        .find_map(|word| word.strip_prefix('=')?.strip_prefix(key)?.strip_prefix('='))
+}
+
+impl Client {
+    pub fn connect(addrs: &[SocketAddr], timeout: Duration) -> Result<Client, String> {
+        let mut last_error = None;
+        for addr in addrs {
+            match TcpStream::connect_timeout(addr, timeout) {
+                Ok(stream) => {
+                    stream
+                        .set_read_timeout(Some(timeout))
+                        .map_err(|e| format!("failed to set timeout: {e}"))?;
+                    return Ok(Client { stream });
+                }
+                Err(e) => last_error = Some(format!("failed to connect to {addr}: {e}")),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| String::from("no router address to connect to")))
+    }
+
+    /// Runs one command and returns its reply sentences, failing on `!trap`
+    /// or `!fatal`.
+    pub fn command(&mut self, words: &[&str]) -> Result<Vec<Vec<String>>, String> {
+        let io_error = |e: io::Error| format!("RouterOS API error: {e}");
+        self.stream
+            .write_all(&encode_sentence(words).map_err(io_error)?)
+            .map_err(io_error)?;
+        let mut replies = Vec::new();
+        loop {
+            let sentence = read_sentence(&mut self.stream).map_err(io_error)?;
+            match sentence.first().map(String::as_str) {
+                Some("!done") => {
+                    replies.push(sentence);
+                    return Ok(replies);
+                }
+                Some("!trap" | "!fatal") => {
+                    let message = attribute(&sentence, "message")
+                        .or_else(|| sentence.get(1).map(String::as_str))
+                        .unwrap_or("unknown error");
+                    return Err(format!("{} failed: {message}", words[0]));
+                }
+                _ => replies.push(sentence),
+            }
+        }
+    }
+
+    /// Logs in with the plain-text method used since RouterOS 6.43.
+    pub fn login(&mut self, user: &str, password: &str) -> Result<(), String> {
+        let name = format!("=name={user}");
+        let password = format!("=password={password}");
+        let replies = self.command(&["/login", &name, &password])?;
+        if replies.iter().any(|s| attribute(s, "ret").is_some()) {
+            return Err(String::from(
+                "router asked for the pre-6.43 challenge login, which is not supported",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Asks the router to send a magic packet with `/tool wol`, optionally out
+    /// of a specific interface.
+    pub fn wake(&mut self, mac: &str, interface: Option<&str>) -> Result<(), String> {
+        let mac = format!("=mac={}", mac.to_ascii_uppercase());
+        let mut words = vec!["/tool/wol", mac.as_str()];
+        let interface = interface.map(|i| format!("=interface={i}"));
+        if let Some(interface) = &interface {
+            words.push(interface);
+        }
+        self.command(&words).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_length_encoding_round_trip() {
+        for length in [
+            0,
+            0x7F,
+            0x80,
+            0x3FFF,
+            0x4000,
+            0x1F_FFFF,
+            0x20_0000,
+            0x1000_0000,
+        ] {
+            let mut encoded = Vec::new();
+            encode_length(length, &mut encoded);
+            assert_eq!(
+                read_length(&mut encoded.as_slice()).unwrap(),
+                usize::try_from(length).unwrap()
+            );
+        }
+        let mut encoded = Vec::new();
+        encode_length(0x80, &mut encoded);
+        assert_eq!(encoded, [0x80, 0x80]);
+    }
+
+    #[test]
+    fn test_sentence_round_trip() {
+        let encoded = encode_sentence(&["/login", "=name=admin"]).unwrap();
+        assert_eq!(&encoded[..7], b"\x06/login");
+        assert_eq!(
+            read_sentence(&mut encoded.as_slice()).unwrap(),
+            ["/login", "=name=admin"]
+        );
+    }
+
+    #[test]
+    fn test_word_length_limit() {
+        let long = "x".repeat(MAX_WORD_LENGTH + 1);
+        assert!(encode_sentence(&[&long]).is_err());
+        assert!(encode_sentence(&[&long[1..]]).is_ok());
+
+        // A peer announcing a 256 MiB word is refused before allocating it.
+        let mut reply = Vec::new();
+        encode_length(0x1000_0000, &mut reply);
+        let err = read_sentence(&mut reply.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_attribute() {
+        let sentence = vec![String::from("!trap"), String::from("=message=no such item")];
+        assert_eq!(attribute(&sentence, "message"), Some("no such item"));
+        assert_eq!(attribute(&sentence, "ret"), None);
+    }
+
+    #[test]
+    fn test_login_and_wake_against_fake_router() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let login = read_sentence(&mut stream).unwrap();
+            stream
+                .write_all(&encode_sentence(&["!done"]).unwrap())
+                .unwrap();
+            let wol = read_sentence(&mut stream).unwrap();
+            stream
+                .write_all(&encode_sentence(&["!trap", "=message=no such interface"]).unwrap())
+                .unwrap();
+            (login, wol)
+        });
+
+        let mut client = Client::connect(&[addr], Duration::from_secs(5)).unwrap();
+        client.login("admin", "secret").unwrap();
+        let err = client
+            .wake("b8:ae:ed:9c:c7:89", Some("ether9"))
+            .unwrap_err();
+        let (login, wol) = router.join().unwrap();
+
+        assert_eq!(login, ["/login", "=name=admin", "=password=secret"]);
+        assert_eq!(
+            wol,
+            ["/tool/wol", "=mac=B8:AE:ED:9C:C7:89", "=interface=ether9"]
+        );
+        assert!(err.contains("no such interface"));
+    }
+}
@@ -0,0 +1,239 @@
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A wall-clock time given with `--at`, resolved against the clock when the
+/// wake is queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    /// Seconds since the epoch as if the time were UTC.
+    naive: i64,
+    /// Seconds east of UTC, when the time carried its own offset.
+    offset: Option<i64>,
+}
+
+impl Time {
+    /// The instant this time names, in seconds since the epoch. A missing
+    /// offset is read as the local one in effect at that instant, which
+    /// `offset_at` gives for any epoch second.
+    fn epoch(self, mut offset_at: impl FnMut(i64) -> i64) -> i64 {
+        if let Some(offset) = self.offset {
+            return self.naive - offset;
+        }
+        // Guess with the offset at the naive instant, then correct once in
+        // case a DST change falls between the guess and the real instant.
+        let guess = self.naive - offset_at(self.naive);
+        self.naive - offset_at(guess)
+    }
+
+    /// How long from `now` until this time, reading a missing offset with
+    /// `offset_at` as in [`local_offset_at`].
+    pub fn until(
+        self,
+        now: SystemTime,
+        offset_at: impl FnMut(i64) -> i64,
+    ) -> Result<Duration, String> {
+        let epoch = self.epoch(offset_at);
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| format!("system clock is before 1970: {e}"))?;
+        u64::try_from(epoch)
+            .ok()
+            .and_then(|epoch| Duration::from_secs(epoch).checked_sub(now))
+            .ok_or_else(|| String::from("the time is in the past"))
+    }
+}
+
+/// Parses a delay such as `90s`, `45m`, `2h` or `1h30m`.
+pub fn parse_delay(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid delay '{text}' (expected e.g. 90s, 45m or 1h30m)");
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+        total = value
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// Parses `YYYY-MM-DD HH:MM[:SS]`, optionally followed by `Z` or a
+/// `+HH:MM`/`-HH:MM` offset. A `T` may stand in for the space.
+pub fn parse_time(text: &str) -> Result<Time, String> {
+    let invalid = || format!("invalid time '{text}' (expected YYYY-MM-DD HH:MM)");
+    let text = text.trim();
+    let (date, rest) = text.split_once([' ', 'T']).ok_or_else(invalid)?;
+    let (clock, offset) = if let Some(clock) = rest.strip_suffix('Z') {
+        (clock, Some(0))
+    } else if let Some(i) = rest.rfind(['+', '-']) {
+        (
+            &rest[..i],
+            Some(parse_offset(&rest[i..]).ok_or_else(invalid)?),
+        )
+    } else {
+        (rest, None)
+    };
+
+    let number = |field: &str| field.parse::<i64>().map_err(|_| invalid());
+    let date: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = date[..] else {
+        return Err(invalid());
+    };
+    let (year, month, day) = (number(year)?, number(month)?, number(day)?);
+    let clock: Vec<&str> = clock.split(':').collect();
+    let (hour, minute, second) = match clock[..] {
+        [hour, minute] => (number(hour)?, number(minute)?, 0),
+        [hour, minute, second] => (number(hour)?, number(minute)?, number(second)?),
+        _ => return Err(invalid()),
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..60).contains(&second)
+    {
+        return Err(invalid());
+    }
+    Ok(Time {
+        naive: days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second,
+        offset,
+    })
+}
+
+fn parse_offset(text: &str) -> Option<i64> {
+    let sign = if text.starts_with('-') { -1 } else { 1 };
+    let digits: String = text.get(1..)?.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The local UTC offset in seconds at `epoch`, as reported by `date +%z`
+/// for that instant (GNU `-d @SECS`, else BSD `-r SECS`), or `None` when
+/// neither works.
+pub fn local_offset_at(epoch: i64) -> Option<i64> {
+    let gnu = [String::from("-d"), format!("@{epoch}")];
+    let bsd = [String::from("-r"), epoch.to_string()];
+    [gnu, bsd].into_iter().find_map(|args| {
+        Command::new("date")
+            .args(args)
+            .arg("+%z")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| parse_offset(String::from_utf8_lossy(&output.stdout).trim()))
+    })
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Converts a civil date to days since 1970-01-01.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Formats a delay as hours, minutes and seconds, e.g. `3h 12m 5s`.
+pub fn format_delay(delay: Duration) -> String {
+    let secs = delay.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{seconds}s"),
+        (0, _) => format!("{minutes}m {seconds}s"),
+        _ => format!("{hours}h {minutes}m {seconds}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delay() {
+        assert_eq!(parse_delay("45m"), Ok(Duration::from_secs(45 * 60)));
+        assert_eq!(parse_delay("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_delay("2d"), Ok(Duration::from_secs(172_800)));
+        assert!(parse_delay("45").is_err());
+        assert!(parse_delay("0s").is_err());
+        assert!(parse_delay("m").is_err());
+        assert!(parse_delay("5w").is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let time = parse_time("2024-06-01 07:00").unwrap();
+        assert_eq!(time.naive, 1_717_225_200);
+        assert_eq!(time.offset, None);
+        assert_eq!(
+            parse_time("2024-06-01T07:00:30+02:00").unwrap(),
+            Time {
+                naive: 1_717_225_230,
+                offset: Some(7200),
+            }
+        );
+        assert_eq!(parse_time("2024-02-29 00:00Z").unwrap().offset, Some(0));
+        assert!(parse_time("2023-02-29 00:00").is_err());
+        assert!(parse_time("2024-06-01 24:00").is_err());
+        assert!(parse_time("07:00").is_err());
+    }
+
+    #[test]
+    fn test_until() {
+        let time = parse_time("2024-06-01 07:00+02:00").unwrap();
+        let now = UNIX_EPOCH + Duration::from_secs(1_717_218_000 - 60);
+        assert_eq!(time.until(now, |_| 0), Ok(Duration::from_secs(60)));
+        let local = parse_time("2024-06-01 07:00").unwrap();
+        assert_eq!(local.until(now, |_| 7200), Ok(Duration::from_secs(60)));
+        assert_eq!(local.until(now, |_| 0), Ok(Duration::from_secs(7260)));
+        assert!(time.until(now + Duration::from_secs(120), |_| 0).is_err());
+    }
+
+    #[test]
+    fn test_epoch_uses_offset_at_target() {
+        // Central Europe moved to +02:00 at 2024-03-31 01:00 UTC.
+        let change = 1_711_846_800;
+        let offset_at = |epoch: i64| if epoch < change { 3600 } else { 7200 };
+        let before = parse_time("2024-03-31 01:30").unwrap();
+        assert_eq!(before.epoch(offset_at), change - 1800);
+        let after = parse_time("2024-03-31 03:30").unwrap();
+        assert_eq!(after.epoch(offset_at), change + 1800);
+        let offset = parse_time("2024-03-31 03:30+01:00").unwrap();
+        assert_eq!(offset.epoch(|_| unreachable!()), change + 5400);
+    }
+
+    #[test]
+    fn test_format_delay() {
+        assert_eq!(format_delay(Duration::from_secs(42)), "42s");
+        assert_eq!(format_delay(Duration::from_secs(11_525)), "3h 12m 5s");
+    }
+}
@@ -0,0 +1,464 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+const PDU_GET_REQUEST: u8 = 0xA0;
+const PDU_RESPONSE: u8 = 0xA2;
+
+const VERSION_2C: i64 = 1;
+
+/// BRIDGE-MIB dot1dTpFdbPort: the bridge port a MAC was last seen on,
+/// indexed by the six MAC octets.
+pub const DOT1D_TP_FDB_PORT: &[u32] = &[1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 2];
+/// BRIDGE-MIB dot1dBasePortIfIndex: the interface behind a bridge port.
+pub const DOT1D_BASE_PORT_IF_INDEX: &[u32] = &[1, 3, 6, 1, 2, 1, 17, 1, 4, 1, 2];
+/// IF-MIB ifName and the older ifDescr, indexed by ifIndex.
+pub const IF_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 1];
+pub const IF_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 2];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    /// The agent has no such object or instance.
+    Missing,
+    Other(u8),
+}
+
+pub fn oid(base: &[u32], index: &[u32]) -> Vec<u32> {
+    let mut oid = base.to_vec();
+    oid.extend_from_slice(index);
+    oid
+}
+
+fn encode_length(length: usize, out: &mut Vec<u8>) {
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes = length.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+}
+
+fn encode_tlv(tag: u8, content: &[u8], out: &mut Vec<u8>) {
+    out.push(tag);
+    encode_length(content.len(), out);
+    out.extend_from_slice(content);
+}
+
+fn encode_integer(value: i64, out: &mut Vec<u8>) {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    // Drop leading bytes that only repeat the sign bit.
+    while start < 7
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    encode_tlv(TAG_INTEGER, &bytes[start..], out);
+}
+
+fn encode_oid(oid: &[u32], out: &mut Vec<u8>) {
+    let mut content = vec![(oid[0] * 40 + oid[1]) as u8];
+    for &arc in &oid[2..] {
+        let mut chunk = vec![(arc & 0x7F) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7F) as u8);
+            rest >>= 7;
+        }
+        content.extend(chunk.iter().rev());
+    }
+    encode_tlv(TAG_OID, &content, out);
+}
+
+/// Builds an SNMPv2c GetRequest for `oids`.
+pub fn get_request(community: &str, request_id: i32, oids: &[Vec<u32>]) -> Vec<u8> {
+    let mut varbinds = Vec::new();
+    for oid in oids {
+        let mut varbind = Vec::new();
+        encode_oid(oid, &mut varbind);
+        encode_tlv(TAG_NULL, &[], &mut varbind);
+        encode_tlv(TAG_SEQUENCE, &varbind, &mut varbinds);
+    }
+
+    let mut pdu = Vec::new();
+    encode_integer(i64::from(request_id), &mut pdu);
+    encode_integer(0, &mut pdu);
+    encode_integer(0, &mut pdu);
+    encode_tlv(TAG_SEQUENCE, &varbinds, &mut pdu);
+
+    let mut message = Vec::new();
+    encode_integer(VERSION_2C, &mut message);
+    encode_tlv(TAG_OCTET_STRING, community.as_bytes(), &mut message);
+    encode_tlv(PDU_GET_REQUEST, &pdu, &mut message);
+
+    let mut out = Vec::new();
+    encode_tlv(TAG_SEQUENCE, &message, &mut out);
+    out
+}
+
+/// Reads one TLV, returning its tag, content and the remaining input.
+fn read_tlv(data: &[u8]) -> Result<(u8, &[u8], &[u8]), String> {
+    let truncated = || String::from("truncated SNMP message");
+    let (&tag, rest) = data.split_first().ok_or_else(truncated)?;
+    let (&first, mut rest) = rest.split_first().ok_or_else(truncated)?;
+    let length = if first & 0x80 == 0 {
+        usize::from(first)
+    } else {
+        let count = usize::from(first & 0x7F);
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(String::from("invalid SNMP length"));
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | usize::from(b));
+        rest = &rest[count..];
+        length
+    };
+    if rest.len() < length {
+        return Err(truncated());
+    }
+    Ok((tag, &rest[..length], &rest[length..]))
+}
+
+fn expect_tlv(data: &[u8], tag: u8) -> Result<(&[u8], &[u8]), String> {
+    let (found, content, rest) = read_tlv(data)?;
+    if found != tag {
+        return Err(format!(
+            "unexpected SNMP tag 0x{found:02x}, expected 0x{tag:02x}"
+        ));
+    }
+    Ok((content, rest))
+}
+
+fn decode_integer(content: &[u8], signed: bool) -> i64 {
+    let negative = signed && content.first().is_some_and(|b| b & 0x80 != 0);
+    content
+        .iter()
+        .fold(if negative { -1 } else { 0 }, |acc, &b| {
+            (acc << 8) | i64::from(b)
+        })
+}
+
+/// Parses the Response PDU answering `request_id`, returning one value per
+/// requested variable binding in order.
+pub fn parse_response(message: &[u8], request_id: i32) -> Result<Vec<Value>, String> {
+    let (message, _) = expect_tlv(message, TAG_SEQUENCE)?;
+    let (_, rest) = expect_tlv(message, TAG_INTEGER)?;
+    let (_, rest) = expect_tlv(rest, TAG_OCTET_STRING)?;
+    let (pdu, _) = expect_tlv(rest, PDU_RESPONSE)?;
+    let (id, rest) = expect_tlv(pdu, TAG_INTEGER)?;
+    if decode_integer(id, true) != i64::from(request_id) {
+        return Err(String::from("SNMP response ID does not match the request"));
+    }
+    let (status, rest) = expect_tlv(rest, TAG_INTEGER)?;
+    let status = decode_integer(status, true);
+    if status != 0 {
+        return Err(format!("SNMP agent returned error status {status}"));
+    }
+    let (_, rest) = expect_tlv(rest, TAG_INTEGER)?;
+    let (mut varbinds, _) = expect_tlv(rest, TAG_SEQUENCE)?;
+
+    let mut values = Vec::new();
+    while !varbinds.is_empty() {
+        let (varbind, rest) = expect_tlv(varbinds, TAG_SEQUENCE)?;
+        varbinds = rest;
+        let (_, value) = expect_tlv(varbind, TAG_OID)?;
+        let (tag, content, _) = read_tlv(value)?;
+        values.push(match tag {
+            TAG_INTEGER => Value::Integer(decode_integer(content, true)),
+            TAG_COUNTER32 | TAG_GAUGE32 | TAG_TIMETICKS => {
+                Value::Integer(decode_integer(content, false))
+            }
+            TAG_OCTET_STRING => Value::OctetString(content.to_vec()),
+            TAG_NO_SUCH_OBJECT | TAG_NO_SUCH_INSTANCE | TAG_END_OF_MIB_VIEW => Value::Missing,
+            other => Value::Other(other),
+        });
+    }
+    Ok(values)
+}
+
+/// Performs an SNMPv2c GET against `agent`, retrying once on timeout.
+pub fn get(
+    agent: SocketAddr,
+    community: &str,
+    oids: &[Vec<u32>],
+    timeout: Duration,
+) -> Result<Vec<Value>, String> {
+    let bind = if agent.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind).map_err(|e| format!("failed to bind socket: {e}"))?;
+    socket
+        .set_read_timeout(Some(timeout))
+        .map_err(|e| format!("failed to set timeout: {e}"))?;
+    let request_id = i32::from(crate::resolve::query_id());
+    let request = get_request(community, request_id, oids);
+
+    let mut buf = [0; 65535];
+    for _ in 0..2 {
+        socket
+            .send_to(&request, agent)
+            .map_err(|e| format!("failed to send SNMP request to {agent}: {e}"))?;
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == agent => {
+                    if let Ok(values) = parse_response(&buf[..len], request_id) {
+                        return Ok(values);
+                    }
+                }
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break;
+                }
+                Err(e) => return Err(format!("failed to receive SNMP response: {e}")),
+            }
+        }
+    }
+    Err(format!("no SNMP response from {agent}"))
+}
+
+/// Where a switch last saw a MAC address.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Location {
+    pub bridge_port: i64,
+    pub if_index: Option<i64>,
+    pub if_name: Option<String>,
+}
+
+/// Looks `mac` up in the switch's forwarding database, returning `None` if
+/// the switch has not learned it.
+pub fn locate(
+    agent: SocketAddr,
+    community: &str,
+    mac: [u8; 6],
+    timeout: Duration,
+) -> Result<Option<Location>, String> {
+    let mac_index: Vec<u32> = mac.iter().map(|&b| u32::from(b)).collect();
+    let port = match get(
+        agent,
+        community,
+        &[oid(DOT1D_TP_FDB_PORT, &mac_index)],
+        timeout,
+    )?
+    .first()
+    {
+        Some(Value::Integer(port)) => *port,
+        _ => return Ok(None),
+    };
+    // A port that can't index the port table is as good as not learned.
+    let Ok(port_index) = u32::try_from(port) else {
+        return Ok(None);
+    };
+
+    let mut location = Location {
+        bridge_port: port,
+        if_index: None,
+        if_name: None,
+    };
+    if let Some(Value::Integer(if_index)) = get(
+        agent,
+        community,
+        &[oid(DOT1D_BASE_PORT_IF_INDEX, &[port_index])],
+        timeout,
+    )?
+    .first()
+        && let Ok(if_index_oid) = u32::try_from(*if_index)
+    {
+        location.if_index = Some(*if_index);
+        let if_index = [if_index_oid];
+        let names = get(
+            agent,
+            community,
+            &[oid(IF_NAME, &if_index), oid(IF_DESCR, &if_index)],
+            timeout,
+        )?;
+        location.if_name = names.into_iter().find_map(|value| match value {
+            Value::OctetString(name) if !name.is_empty() => {
+                Some(String::from_utf8_lossy(&name).into_owned())
+            }
+            _ => None,
+        });
+    }
+    Ok(Some(location))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_integer() {
+        let mut out = Vec::new();
+        encode_integer(0, &mut out);
+        encode_integer(128, &mut out);
+        encode_integer(-1, &mut out);
+        assert_eq!(out, [0x02, 1, 0x00, 0x02, 2, 0x00, 0x80, 0x02, 1, 0xFF]);
+    }
+
+    #[test]
+    fn test_encode_oid() {
+        let mut out = Vec::new();
+        encode_oid(&[1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 2, 184], &mut out);
+        assert_eq!(
+            out,
+            [0x06, 12, 0x2b, 6, 1, 2, 1, 17, 4, 3, 1, 2, 0x81, 0x38]
+        );
+    }
+
+    #[test]
+    fn test_encode_long_length() {
+        let mut out = Vec::new();
+        encode_length(300, &mut out);
+        assert_eq!(out, [0x82, 0x01, 0x2C]);
+    }
+
+    #[test]
+    fn test_get_request() {
+        let request = get_request("public", 1, &[vec![1, 3, 6, 1]]);
+        let mut expected = vec![0x30, 0x21, 0x02, 0x01, 0x01, 0x04, 0x06];
+        expected.extend_from_slice(b"public");
+        expected.extend_from_slice(&[
+            0xa0, 0x14, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x09, 0x30,
+            0x07, 0x06, 0x03, 0x2b, 0x06, 0x01, 0x05, 0x00,
+        ]);
+        assert_eq!(request, expected);
+    }
+
+    fn response(request_id: i32, status: i64, values: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut varbinds = Vec::new();
+        for (tag, content) in values {
+            let mut varbind = Vec::new();
+            encode_oid(&[1, 3, 6, 1], &mut varbind);
+            encode_tlv(*tag, content, &mut varbind);
+            encode_tlv(TAG_SEQUENCE, &varbind, &mut varbinds);
+        }
+        let mut pdu = Vec::new();
+        encode_integer(i64::from(request_id), &mut pdu);
+        encode_integer(status, &mut pdu);
+        encode_integer(0, &mut pdu);
+        encode_tlv(TAG_SEQUENCE, &varbinds, &mut pdu);
+        let mut message = Vec::new();
+        encode_integer(VERSION_2C, &mut message);
+        encode_tlv(TAG_OCTET_STRING, b"public", &mut message);
+        encode_tlv(PDU_RESPONSE, &pdu, &mut message);
+        let mut out = Vec::new();
+        encode_tlv(TAG_SEQUENCE, &message, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_parse_response_values() {
+        let message = response(
+            42,
+            0,
+            &[
+                (TAG_INTEGER, &[0x0c]),
+                (TAG_OCTET_STRING, b"Gi1/0/12"),
+                (TAG_NO_SUCH_INSTANCE, &[]),
+                (TAG_GAUGE32, &[0xFF, 0xFF]),
+            ],
+        );
+        assert_eq!(
+            parse_response(&message, 42).unwrap(),
+            [
+                Value::Integer(12),
+                Value::OctetString(b"Gi1/0/12".to_vec()),
+                Value::Missing,
+                Value::Integer(0xFFFF),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_errors() {
+        assert!(parse_response(&response(42, 0, &[]), 43).is_err());
+        assert!(parse_response(&response(42, 2, &[]), 42).is_err());
+        assert!(parse_response(&[0x30, 0x05, 0x02], 42).is_err());
+    }
+
+    /// Answers one GET request per entry of `replies` with those values.
+    fn fake_agent(replies: Vec<&'static [(u8, &'static [u8])]>) -> SocketAddr {
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = agent.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = [0; 1500];
+            for values in replies {
+                let (len, from) = agent.recv_from(&mut buf).unwrap();
+                let (message, _) = expect_tlv(&buf[..len], TAG_SEQUENCE).unwrap();
+                let (_, rest) = expect_tlv(message, TAG_INTEGER).unwrap();
+                let (_, rest) = expect_tlv(rest, TAG_OCTET_STRING).unwrap();
+                let (pdu, _) = expect_tlv(rest, PDU_GET_REQUEST).unwrap();
+                let (id, _) = expect_tlv(pdu, TAG_INTEGER).unwrap();
+                let id = decode_integer(id, true) as i32;
+                agent.send_to(&response(id, 0, values), from).unwrap();
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn test_locate_against_fake_agent() {
+        let address = fake_agent(vec![
+            &[(TAG_INTEGER, &[7])],
+            &[(TAG_INTEGER, &[0x27, 0x1b])],
+            &[(TAG_OCTET_STRING, b"Gi1/0/7"), (TAG_OCTET_STRING, b"")],
+        ]);
+        let location = locate(
+            address,
+            "public",
+            [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89],
+            Duration::from_secs(5),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            location,
+            Location {
+                bridge_port: 7,
+                if_index: Some(10011),
+                if_name: Some(String::from("Gi1/0/7")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_locate_out_of_range_indexes() {
+        let mac = [0xb8, 0xae, 0xed, 0x9c, 0xc7, 0x89];
+        let timeout = Duration::from_secs(5);
+        let address = fake_agent(vec![&[(TAG_INTEGER, &[0xff])]]);
+        assert_eq!(locate(address, "public", mac, timeout), Ok(None));
+
+        let address = fake_agent(vec![&[(TAG_INTEGER, &[7])], &[(TAG_INTEGER, &[0xff])]]);
+        assert_eq!(
+            locate(address, "public", mac, timeout),
+            Ok(Some(Location {
+                bridge_port: 7,
+                if_index: None,
+                if_name: None,
+            }))
+        );
+    }
+}
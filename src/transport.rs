@@ -0,0 +1,259 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+#[cfg(any(test, feature = "test-util"))]
+use std::collections::VecDeque;
+
+/// Sends and receives packets on behalf of a [`WakeRequest`](crate::WakeRequest)
+/// and other senders.
+pub trait Transport {
+    /// Sends `payload` as one UDP datagram to `destination`, with
+    /// broadcasting allowed, and returns the local address it left from.
+    fn send_udp(&mut self, destination: SocketAddr, payload: &[u8]) -> io::Result<SocketAddr>;
+
+    /// Sends a complete Ethernet frame out of `interface`.
+    fn send_raw(&mut self, interface: &str, frame: &[u8]) -> io::Result<()> {
+        let _ = (interface, frame);
+        Err(unsupported("sending raw frames"))
+    }
+
+    /// Waits up to `timeout` for a datagram or frame seen on `port`.
+    /// Returns `None` when nothing arrived in time.
+    fn sniff(&mut self, port: u16, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+        let _ = (port, timeout);
+        Err(unsupported("sniffing"))
+    }
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{what} isn't supported by this transport"),
+    )
+}
+
+/// The operating system's UDP sockets. One socket per address family is
+/// bound on first use and reused, so repeated sends share a source port.
+/// Raw frames aren't supported.
+#[derive(Debug, Default)]
+pub struct UdpTransport {
+    v4: Option<UdpSocket>,
+    v6: Option<UdpSocket>,
+}
+
+impl UdpTransport {
+    pub fn new() -> Self {
+        UdpTransport::default()
+    }
+
+    fn socket(&mut self, destination: SocketAddr) -> io::Result<&UdpSocket> {
+        let (slot, bind): (_, IpAddr) = if destination.is_ipv4() {
+            (&mut self.v4, Ipv4Addr::UNSPECIFIED.into())
+        } else {
+            (&mut self.v6, Ipv6Addr::UNSPECIFIED.into())
+        };
+        if slot.is_none() {
+            let socket = UdpSocket::bind((bind, 0))?;
+            socket.set_broadcast(true)?;
+            *slot = Some(socket);
+        }
+        Ok(slot.as_ref().expect("socket was just bound"))
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_udp(&mut self, destination: SocketAddr, payload: &[u8]) -> io::Result<SocketAddr> {
+        let socket = self.socket(destination)?;
+        socket.send_to(payload, destination)?;
+        socket.local_addr()
+    }
+
+    /// Receives one UDP datagram sent to `port` on any local address.
+    fn sniff(&mut self, port: u16, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_read_timeout(Some(timeout))?;
+        let mut buf = [0; 2048];
+        match socket.recv_from(&mut buf) {
+            Ok((len, _)) => Ok(Some(buf[..len].to_vec())),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A packet handed to a [`MockTransport`].
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Udp {
+        destination: SocketAddr,
+        payload: Vec<u8>,
+    },
+    Raw {
+        interface: String,
+        frame: Vec<u8>,
+    },
+}
+
+/// The source port every [`MockTransport`] send reports.
+#[cfg(any(test, feature = "test-util"))]
+pub const MOCK_SOURCE_PORT: u16 = 49152;
+
+/// An in-memory transport that records what is sent and replays queued
+/// frames to [`sniff`](Transport::sniff), without touching the network or
+/// waiting. Available with the `test-util` feature.
+///
+/// ```
+/// use wake_on_lan::WakeRequest;
+/// use wake_on_lan::transport::{MockTransport, Packet};
+///
+/// let mut transport = MockTransport::new();
+/// let request = WakeRequest::builder().mac("b8:ae:ed:9c:c7:89").build()?;
+/// request.send_with(&mut transport)?;
+/// assert_eq!(
+///     transport.sent(),
+///     [Packet::Udp { destination: request.destination(), payload: request.packet().to_vec() }]
+/// );
+/// # Ok::<(), wake_on_lan::Error>(())
+/// ```
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    sent: Vec<Packet>,
+    inbound: VecDeque<Vec<u8>>,
+    failures: VecDeque<io::ErrorKind>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl MockTransport {
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+
+    /// Everything sent so far, oldest first.
+    pub fn sent(&self) -> &[Packet] {
+        &self.sent
+    }
+
+    /// Queues a frame for a later [`sniff`](Transport::sniff) to return.
+    pub fn push_inbound(&mut self, frame: impl Into<Vec<u8>>) {
+        self.inbound.push_back(frame.into());
+    }
+
+    /// Makes the next call, of any kind, fail with `kind` instead of
+    /// succeeding. Queued failures are used in order.
+    pub fn fail_next(&mut self, kind: io::ErrorKind) {
+        self.failures.push_back(kind);
+    }
+
+    fn check_failure(&mut self) -> io::Result<()> {
+        match self.failures.pop_front() {
+            Some(kind) => Err(io::Error::new(kind, "injected by MockTransport")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Transport for MockTransport {
+    /// Records the datagram and reports it as sent from port
+    /// [`MOCK_SOURCE_PORT`] on the unspecified address of its family.
+    fn send_udp(&mut self, destination: SocketAddr, payload: &[u8]) -> io::Result<SocketAddr> {
+        self.check_failure()?;
+        self.sent.push(Packet::Udp {
+            destination,
+            payload: payload.to_vec(),
+        });
+        let source: IpAddr = if destination.is_ipv4() {
+            Ipv4Addr::UNSPECIFIED.into()
+        } else {
+            Ipv6Addr::UNSPECIFIED.into()
+        };
+        Ok(SocketAddr::new(source, MOCK_SOURCE_PORT))
+    }
+
+    fn send_raw(&mut self, interface: &str, frame: &[u8]) -> io::Result<()> {
+        self.check_failure()?;
+        self.sent.push(Packet::Raw {
+            interface: interface.to_owned(),
+            frame: frame.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Returns the oldest queued frame whatever the port, or `None` right
+    /// away when the queue is empty.
+    fn sniff(&mut self, _port: u16, _timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+        self.check_failure()?;
+        Ok(self.inbound.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_udp_transport_reuses_socket() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = receiver.local_addr().unwrap();
+        let mut transport = UdpTransport::new();
+        let first = transport.send_udp(addr, b"one").unwrap();
+        let second = transport.send_udp(addr, b"two").unwrap();
+        assert_eq!(first, second);
+
+        let mut buf = [0; 8];
+        let (len, from) = receiver.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"one");
+        assert_eq!(from.port(), first.port());
+        assert_eq!(
+            transport.send_raw("eth0", &[]).unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn test_mock_transport() {
+        let mut transport = MockTransport::new();
+        let destination: SocketAddr = "[ff02::1]:9".parse().unwrap();
+        assert_eq!(
+            transport.send_udp(destination, b"wake").unwrap(),
+            SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), MOCK_SOURCE_PORT)
+        );
+        transport.send_raw("eth0", b"frame").unwrap();
+        assert_eq!(
+            transport.sent(),
+            [
+                Packet::Udp {
+                    destination,
+                    payload: b"wake".to_vec(),
+                },
+                Packet::Raw {
+                    interface: String::from("eth0"),
+                    frame: b"frame".to_vec(),
+                },
+            ]
+        );
+
+        transport.push_inbound(b"reply".to_vec());
+        transport.fail_next(io::ErrorKind::PermissionDenied);
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            transport.sniff(9, timeout).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            transport.sniff(9, timeout).unwrap(),
+            Some(b"reply".to_vec())
+        );
+        assert_eq!(transport.sniff(9, timeout).unwrap(), None);
+    }
+}